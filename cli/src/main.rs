@@ -0,0 +1,389 @@
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use substr_core::{
+    Algorithm, AnalysisConfig, Corpus, Monitor, ResponseAndOverall, SelfComparison, Tokenization, dictionary::parse_dictionary,
+    normalize::CharClass, phonetic::parse_readings, synonyms::Synonym, variants::parse_variant_groups,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "substr",
+    version,
+    about = "Compare two texts, every pair of same-named files in two directories, or several texts with each other, \
+             or find the passages a text repeats"
+)]
+struct Args {
+    /// First text file, or a directory of text files
+    a: PathBuf,
+    /// Second text file, or a directory holding files with the same names. Without it, the
+    /// first text is compared with itself
+    b: Option<PathBuf>,
+    /// More text files, to compare every file with every other one
+    others: Vec<PathBuf>,
+    /// JSON analysis config, the options below override its values
+    #[arg(long)]
+    config: Option<PathBuf>,
+    #[arg(long)]
+    min_length: Option<usize>,
+    #[arg(long)]
+    ratio: Option<f32>,
+    #[arg(long)]
+    max_strikes: Option<usize>,
+    #[arg(long)]
+    max_substrings: Option<usize>,
+    #[arg(long)]
+    kernel_size: Option<usize>,
+    #[arg(long)]
+    base_match_size: Option<usize>,
+    /// Grow the exact matches of the suffix array through edits, as far as the ratio allows
+    #[arg(long)]
+    extend_repeats: bool,
+    #[arg(long, value_enum)]
+    algorithm: Option<AlgorithmArg>,
+    #[arg(long, value_enum)]
+    tokenization: Option<TokenizationArg>,
+    /// How the copies of a passage repeated within one text may overlap, overlapping by
+    /// default when only one text is given
+    #[arg(long, value_enum)]
+    self_comparison: Option<SelfComparisonArg>,
+    /// Smith-Waterman score for matching tokens
+    #[arg(long, allow_negative_numbers = true)]
+    match_score: Option<i32>,
+    /// Smith-Waterman score for mismatched tokens
+    #[arg(long, allow_negative_numbers = true)]
+    mismatch_score: Option<i32>,
+    /// Smith-Waterman score for gaps
+    #[arg(long, allow_negative_numbers = true)]
+    gap_score: Option<i32>,
+    /// Edit ratio cost of a token that only B has
+    #[arg(long)]
+    insertion_cost: Option<f32>,
+    /// Edit ratio cost of a token that only A has
+    #[arg(long)]
+    deletion_cost: Option<f32>,
+    /// Edit ratio cost of a token of A replaced by another one in B
+    #[arg(long)]
+    substitution_cost: Option<f32>,
+    /// Edit ratio cost of swapping two neighbouring tokens, which otherwise takes two edits
+    #[arg(long)]
+    transposition_cost: Option<f32>,
+    /// Table of variant characters that should match each other, one group per line
+    #[arg(long)]
+    variants: Vec<PathBuf>,
+    /// Glossary of word forms that should match each other, one tab separated group per line
+    #[arg(long)]
+    dictionary: Vec<PathBuf>,
+    /// Table of characters and their readings, so that homophones match each other
+    #[arg(long)]
+    readings: Vec<PathBuf>,
+    /// Edit ratio cost of a character replaced by a homophone, 0 makes them the same character
+    #[arg(long)]
+    phonetic_cost: Option<f32>,
+    /// JSON list of synonyms for the first text, in the format of a project file
+    #[arg(long)]
+    synonyms_a: Option<PathBuf>,
+    /// JSON list of synonyms for the second text, in the format of a project file
+    #[arg(long)]
+    synonyms_b: Option<PathBuf>,
+    /// Keep every character instead of dropping the classes in the config's normalization,
+    /// or the classic punctuation marks if it has none and tokens are not words. Either way,
+    /// offsets in synonym files refer to the files as written, and so do the offsets in the
+    /// output unless the config sets originalOffsets to false
+    #[arg(long)]
+    keep_punctuation: bool,
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AlgorithmArg {
+    Comparativus,
+    SmithWaterman,
+    GreedyStringTiling,
+    SuffixArray,
+}
+
+impl From<AlgorithmArg> for Algorithm {
+    fn from(algorithm: AlgorithmArg) -> Self {
+        match algorithm {
+            AlgorithmArg::Comparativus => Algorithm::Comparativus,
+            AlgorithmArg::SmithWaterman => Algorithm::SmithWaterman,
+            AlgorithmArg::GreedyStringTiling => Algorithm::GreedyStringTiling,
+            AlgorithmArg::SuffixArray => Algorithm::SuffixArray,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TokenizationArg {
+    Character,
+    Grapheme,
+    Word,
+}
+
+impl From<TokenizationArg> for Tokenization {
+    fn from(tokenization: TokenizationArg) -> Self {
+        match tokenization {
+            TokenizationArg::Character => Tokenization::Character,
+            TokenizationArg::Grapheme => Tokenization::Grapheme,
+            TokenizationArg::Word => Tokenization::Word,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SelfComparisonArg {
+    Off,
+    Overlapping,
+    Disjoint,
+}
+
+impl From<SelfComparisonArg> for SelfComparison {
+    fn from(self_comparison: SelfComparisonArg) -> Self {
+        match self_comparison {
+            SelfComparisonArg::Off => SelfComparison::Off,
+            SelfComparisonArg::Overlapping => SelfComparison::Overlapping,
+            SelfComparisonArg::Disjoint => SelfComparison::Disjoint,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Serialize)]
+struct Comparison {
+    file_a: String,
+    file_b: String,
+    #[serde(flatten)]
+    response: ResponseAndOverall,
+}
+
+// Pairs up files with the same name when both inputs are directories
+fn collect_pairs(a: &Path, b: &Path) -> Result<Vec<(PathBuf, PathBuf)>, Box<dyn Error>> {
+    if a.is_dir() != b.is_dir() {
+        return Err("both inputs must be files, or both must be directories".into());
+    }
+    if !a.is_dir() {
+        return Ok(vec![(a.to_path_buf(), b.to_path_buf())]);
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(a)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    files.retain(|path| path.is_file());
+    files.sort();
+    let mut pairs = Vec::with_capacity(files.len());
+    for file_a in files {
+        let file_b = b.join(file_a.file_name().unwrap());
+        if file_b.is_file() {
+            pairs.push((file_a, file_b));
+        } else {
+            eprintln!("Skipping {}: no file with the same name in {}", file_a.display(), b.display());
+        }
+    }
+    Ok(pairs)
+}
+
+fn override_with<T>(value: &mut T, arg: Option<T>) {
+    if let Some(arg) = arg {
+        *value = arg;
+    }
+}
+
+fn read_config(args: &Args) -> Result<AnalysisConfig, Box<dyn Error>> {
+    let mut config: AnalysisConfig = match &args.config {
+        Some(path) => serde_json::from_str(&fs::read_to_string(path)?)?,
+        None => AnalysisConfig::default(),
+    };
+    override_with(&mut config.algorithm, args.algorithm.map(Into::into));
+    override_with(&mut config.tokenization, args.tokenization.map(Into::into));
+    override_with(&mut config.min_length, args.min_length);
+    override_with(&mut config.ratio, args.ratio);
+    override_with(&mut config.max_strikes, args.max_strikes);
+    override_with(&mut config.max_substrings, args.max_substrings);
+    override_with(&mut config.kernel_size, args.kernel_size);
+    override_with(&mut config.base_match_size, args.base_match_size);
+    config.extend_repeats |= args.extend_repeats;
+    override_with(&mut config.self_comparison, args.self_comparison.map(Into::into));
+    if args.b.is_none() && args.self_comparison.is_none() && config.self_comparison == SelfComparison::Off {
+        config.self_comparison = SelfComparison::Overlapping;
+    }
+    override_with(&mut config.scoring.match_score, args.match_score);
+    override_with(&mut config.scoring.mismatch_score, args.mismatch_score);
+    override_with(&mut config.scoring.gap_score, args.gap_score);
+    override_with(&mut config.costs.insertion, args.insertion_cost);
+    override_with(&mut config.costs.deletion, args.deletion_cost);
+    override_with(&mut config.costs.substitution, args.substitution_cost);
+    override_with(&mut config.costs.transposition, args.transposition_cost.map(Some));
+    for path in &args.variants {
+        config.variants.extend(parse_variant_groups(&fs::read_to_string(path)?));
+    }
+    for path in &args.dictionary {
+        config.dictionary.extend(parse_dictionary(&fs::read_to_string(path)?));
+    }
+    for path in &args.readings {
+        config.phonetic.readings.extend(parse_readings(&fs::read_to_string(path)?));
+    }
+    override_with(&mut config.phonetic.cost, args.phonetic_cost);
+    // Words are split at whitespace and punctuation, so dropping them would merge the words
+    let by_word = config.tokenization == Tokenization::Word;
+    let drop = &mut config.normalization.drop;
+    if args.keep_punctuation {
+        drop.clear();
+    } else if drop.is_empty() && !by_word {
+        drop.push(CharClass::Classic);
+    }
+    Ok(config)
+}
+
+fn read_synonyms(path: &Option<PathBuf>) -> Result<Vec<Synonym>, Box<dyn Error>> {
+    match path {
+        Some(path) => Ok(serde_json::from_str(&fs::read_to_string(path)?)?),
+        None => Ok(vec![]),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv(out: &mut impl Write, comparisons: &[Comparison]) -> io::Result<()> {
+    writeln!(
+        out,
+        "file_a,file_b,overall_levenstein_similarity,overall_cosine_similarity,a_start,a_end,b_start,b_end,similarity,levenshtein_match,phonetic"
+    )?;
+    for comparison in comparisons {
+        for result in &comparison.response.result {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                csv_field(&comparison.file_a),
+                csv_field(&comparison.file_b),
+                comparison.response.overall_levenstein_similarity,
+                comparison.response.overall_cosine_similarity,
+                result.a.start,
+                result.a.end,
+                result.b.start,
+                result.b.end,
+                result.similarity,
+                result.levenshtein_match,
+                result.phonetic,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// A pair that cannot be compared is reported and left out, the others are still compared.
+// Returns the comparisons and how many pairs failed.
+fn compare_pairs(args: &Args, config: &AnalysisConfig) -> Result<(Vec<Comparison>, usize), Box<dyn Error>> {
+    if args.a.is_dir() && (args.synonyms_a.is_some() || args.synonyms_b.is_some()) {
+        return Err("synonyms point into one pair of texts, so they cannot be given for directories".into());
+    }
+    let synonyms_a = read_synonyms(&args.synonyms_a)?;
+    let synonyms_b = read_synonyms(&args.synonyms_b)?;
+
+    let mut comparisons = Vec::new();
+    let mut failed = 0;
+    for (file_a, file_b) in collect_pairs(&args.a, args.b.as_ref().unwrap_or(&args.a))? {
+        let compare = || -> Result<ResponseAndOverall, Box<dyn Error>> {
+            let text_a = fs::read_to_string(&file_a)?;
+            let text_b = fs::read_to_string(&file_b)?;
+            Ok(substr_core::analyze(&text_a, &text_b, synonyms_a.clone(), synonyms_b.clone(), config)?)
+        };
+        let response = match compare() {
+            Ok(response) => response,
+            Err(e) => {
+                eprintln!("{} and {}: {}", file_a.display(), file_b.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        for warning in &response.warnings {
+            eprintln!("{} and {}: {}", file_a.display(), file_b.display(), warning);
+        }
+        comparisons.push(Comparison {
+            file_a: file_a.display().to_string(),
+            file_b: file_b.display().to_string(),
+            response,
+        });
+    }
+    Ok((comparisons, failed))
+}
+
+// Every file is tokenized once and then compared with every file after it
+fn compare_corpus(args: &Args, config: &AnalysisConfig) -> Result<Vec<Comparison>, Box<dyn Error>> {
+    if args.synonyms_a.is_some() || args.synonyms_b.is_some() {
+        return Err("synonyms can only be given for two texts".into());
+    }
+    let files: Vec<&PathBuf> = [&args.a].into_iter().chain(&args.b).chain(&args.others).collect();
+    if files.iter().any(|file| file.is_dir()) {
+        return Err("with more than two inputs, all of them must be files".into());
+    }
+    let texts = files.iter().map(fs::read_to_string).collect::<Result<Vec<_>, _>>()?;
+    let mut corpus = Corpus::new(&texts);
+    corpus.set_config(config.clone());
+    let response = corpus.run(&mut Monitor::default())?;
+    let comparisons = response
+        .pairs
+        .into_iter()
+        .map(|pair| {
+            let (file_a, file_b) = (files[pair.a].display(), files[pair.b].display());
+            for warning in &pair.response.warnings {
+                eprintln!("{} and {}: {}", file_a, file_b, warning);
+            }
+            Comparison {
+                file_a: file_a.to_string(),
+                file_b: file_b.to_string(),
+                response: pair.response,
+            }
+        })
+        .collect();
+    Ok(comparisons)
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let config = read_config(&args)?;
+    let (comparisons, failed) = if args.others.is_empty() {
+        compare_pairs(&args, &config)?
+    } else {
+        (compare_corpus(&args, &config)?, 0)
+    };
+
+    let mut out = io::stdout().lock();
+    match args.format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &comparisons)?;
+            writeln!(out)?;
+        }
+        Format::Csv => write_csv(&mut out, &comparisons)?,
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} pairs could not be compared", failed, failed + comparisons.len()).into());
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("substr: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
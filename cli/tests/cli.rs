@@ -0,0 +1,121 @@
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+const A: &str = "學而時習之，不亦說乎？有朋自遠方來，不亦樂乎？人不知而不慍，不亦君子乎？";
+const B: &str = "學而時習之，不亦悅乎！有朋自遠方來，不亦樂乎？人不知而不慍，不亦君子乎";
+
+// A fresh directory for one test, with the files given
+fn directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("substr-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, text) in files {
+        fs::write(dir.join(file), text).unwrap();
+    }
+    dir
+}
+
+fn substr(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_substr")).args(args).output().unwrap()
+}
+
+fn compared(output: &Output) -> Vec<(String, String)> {
+    let comparisons: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let name = |value: &Value| Path::new(value.as_str().unwrap()).file_name().unwrap().to_string_lossy().into_owned();
+    comparisons.iter().map(|c| (name(&c["file_a"]), name(&c["file_b"]))).collect()
+}
+
+#[test]
+fn compares_two_files() {
+    let dir = directory("files", &[("a.txt", A), ("b.txt", B)]);
+    let output = substr(&[&dir.join("a.txt"), &dir.join("b.txt")]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(compared(&output), [("a.txt".to_string(), "b.txt".to_string())]);
+    let comparisons: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let results = comparisons[0]["result"].as_array().unwrap();
+    assert!(results.iter().any(|result| result["levenshteinMatch"] == true));
+    // The offsets point into the files as written, so the last match ends before the last question mark
+    let ends = results.iter().map(|result| result["a"]["end"].as_u64().unwrap());
+    assert_eq!(ends.max(), Some(A.chars().count() as u64 - 1));
+}
+
+#[test]
+fn directories_compare_every_pair_that_can_be() {
+    let a = directory("dir-a", &[("1.txt", A), ("2.txt", ""), ("3.txt", B), ("4.txt", "學")]);
+    let b = directory("dir-b", &[("1.txt", B), ("2.txt", B), ("3.txt", A), ("4.txt", B)]);
+    // A file that is not UTF-8 cannot be read as text
+    fs::write(a.join("2.txt"), [0xff, 0xfe]).unwrap();
+    let output = substr(&[&a, &b]);
+    // The unreadable file and the text that is too short fail their pairs only
+    assert!(!output.status.success());
+    let pairs = [("1.txt", "1.txt"), ("3.txt", "3.txt")].map(|(x, y)| (x.to_string(), y.to_string()));
+    assert_eq!(compared(&output), pairs);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("2.txt") && stderr.contains("UTF-8"), "{}", stderr);
+    assert!(stderr.contains("4.txt") && stderr.contains("too short"), "{}", stderr);
+    assert!(stderr.contains("2 of 4 pairs"), "{}", stderr);
+}
+
+#[test]
+fn directories_take_no_synonyms() {
+    let a = directory("synonyms-a", &[("1.txt", A)]);
+    let b = directory("synonyms-b", &[("1.txt", B)]);
+    let synonyms = a.join("synonyms.json");
+    fs::write(&synonyms, r#"[{"word": {"start": 7, "end": 8}, "synonyms": [{"start": 7, "end": 8}]}]"#).unwrap();
+    let output = substr(&[&a, &b, Path::new("--synonyms-a"), &synonyms]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("synonyms"));
+}
+
+#[test]
+fn one_file_is_compared_with_itself() {
+    let twice = format!("{}{}", A, A);
+    let dir = directory("self", &[("a.txt", &twice)]);
+    let output = substr(&[&dir.join("a.txt"), Path::new("--self-comparison"), Path::new("disjoint")]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(compared(&output), [("a.txt".to_string(), "a.txt".to_string())]);
+    let comparisons: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let results = comparisons[0]["result"].as_array().unwrap();
+    let repeats: Vec<_> = results.iter().filter(|result| result["levenshteinMatch"] == true).collect();
+    // The second copy is found as a repeat of the first, and no passage is paired with itself
+    assert!(!repeats.is_empty());
+    for repeat in repeats {
+        assert!(repeat["a"]["end"].as_u64() <= repeat["b"]["start"].as_u64(), "{}", repeat);
+    }
+    let overall = comparisons[0]["overall_levenstein_similarity"].as_f64().unwrap();
+    assert!(overall > 0.0 && overall <= 1.0, "{}", overall);
+}
+
+#[test]
+fn writes_csv() {
+    let dir = directory("csv", &[("a.txt", A), ("b.txt", B)]);
+    let output = substr(&[&dir.join("a.txt"), &dir.join("b.txt"), Path::new("--format"), Path::new("csv")]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert!(lines.next().unwrap().starts_with("file_a,file_b,overall_levenstein_similarity,"));
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    assert!(!rows.is_empty());
+    for row in &rows {
+        assert_eq!(row.len(), 11, "{:?}", row);
+        assert!(row[0].ends_with("a.txt") && row[1].ends_with("b.txt"), "{:?}", row);
+    }
+    assert!(rows.iter().any(|row| row[9] == "true"));
+}
+
+#[test]
+fn more_files_compare_every_file_with_every_other_one() {
+    let dir = directory("corpus", &[("a.txt", A), ("b.txt", B), ("c.txt", A)]);
+    let output = substr(&[&dir.join("a.txt"), &dir.join("b.txt"), &dir.join("c.txt")]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let pairs = [("a.txt", "b.txt"), ("a.txt", "c.txt"), ("b.txt", "c.txt")].map(|(x, y)| (x.to_string(), y.to_string()));
+    assert_eq!(compared(&output), pairs);
+    // The same text twice is found whole
+    let comparisons: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(comparisons[1]["overall_levenstein_similarity"], 1.0);
+}
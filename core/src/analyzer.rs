@@ -0,0 +1,622 @@
+use crate::{
+    Algorithm, ResponseAndOverall, SelfComparison, Tokenization,
+    comparativus::{self, SharedNgrams},
+    config::AnalysisConfig,
+    costs::EditCosts,
+    dictionary::Dictionary,
+    error::Error,
+    greedy_string_tiling,
+    normalize::OffsetMap,
+    progress::Monitor,
+    smith_waterman, suffix_array,
+    synonyms::{self, LinkedWords, Synonym, SynonymClass, Token, Vocabulary, Word},
+    tokenizer::{self, Tokenizer},
+    utils::{Matches, Substring},
+    variants::VariantTable,
+};
+use std::borrow::Cow;
+
+// The token streams of A and B, with the synonym classes their keys come from and the
+// substitution costs between them
+struct Tokenized {
+    token_a: Vec<Token>,
+    token_b: Vec<Token>,
+    // The streams the algorithms search, if free homophones share a key in them
+    homophones: Option<(Vec<Token>, Vec<Token>)>,
+    classes: Vec<SynonymClass>,
+    costs: EditCosts,
+}
+
+// An analysis session over two fixed texts. The token streams and the n-gram index
+// are kept between runs and only rebuilt when the synonyms or the kernel size change.
+pub struct Analyzer {
+    str_a: String,
+    str_b: String,
+    // The texts that are analysed, which differ from the ones given once they are normalized
+    file_a: Vec<char>,
+    file_b: Vec<char>,
+    offsets: Option<(OffsetMap, OffsetMap)>,
+    synonyms_a: Vec<Synonym>,
+    synonyms_b: Vec<Synonym>,
+    config: AnalysisConfig,
+    // Replaces the tokenizer picked by the config's tokenization
+    tokenizer: Option<Box<dyn Tokenizer>>,
+    tokens: Option<Tokenized>,
+    ngrams: Option<SharedNgrams>,
+}
+
+impl Analyzer {
+    pub fn new(str_a: &str, str_b: &str) -> Self {
+        Analyzer {
+            str_a: str_a.to_string(),
+            str_b: str_b.to_string(),
+            file_a: str_a.chars().collect(),
+            file_b: str_b.chars().collect(),
+            offsets: None,
+            synonyms_a: Vec::new(),
+            synonyms_b: Vec::new(),
+            config: AnalysisConfig::default(),
+            tokenizer: None,
+            tokens: None,
+            ngrams: None,
+        }
+    }
+
+    pub fn set_config(&mut self, config: AnalysisConfig) {
+        if self.ngrams.as_ref().is_some_and(|ngrams| ngrams.kernel_size != config.kernel_size) {
+            self.ngrams = None;
+        }
+        let reload = config.normalization != self.config.normalization;
+        if config.variants != self.config.variants
+            || config.tokenization != self.config.tokenization
+            || config.dictionary != self.config.dictionary
+            || config.costs != self.config.costs
+            || config.phonetic != self.config.phonetic
+            || config.self_comparison != self.config.self_comparison
+        {
+            self.tokens = None;
+            self.ngrams = None;
+        }
+        self.config = config;
+        if reload {
+            self.load_texts();
+        }
+    }
+
+    fn load_texts(&mut self) {
+        if self.config.normalization.is_identity() {
+            self.file_a = self.str_a.chars().collect();
+            self.file_b = self.str_b.chars().collect();
+            self.offsets = None;
+        } else {
+            let (normalized_a, offsets_a) = self.config.normalization.apply(&self.str_a);
+            let (normalized_b, offsets_b) = self.config.normalization.apply(&self.str_b);
+            self.file_a = normalized_a.chars().collect();
+            self.file_b = normalized_b.chars().collect();
+            self.offsets = Some((offsets_a, offsets_b));
+        }
+        self.tokens = None;
+        self.ngrams = None;
+    }
+
+    // Segments the texts with a tokenizer of the caller's own instead of the built-in ones
+    pub fn set_tokenizer(&mut self, tokenizer: Box<dyn Tokenizer>) {
+        self.tokenizer = Some(tokenizer);
+        self.tokens = None;
+        self.ngrams = None;
+    }
+
+    // Synonyms link tokens across both texts, so both token streams are rebuilt
+    pub fn set_synonyms(&mut self, mut synonyms_a: Vec<Synonym>, mut synonyms_b: Vec<Synonym>) {
+        synonyms_a.sort_unstable_by_key(|s| s.word.start);
+        synonyms_b.sort_unstable_by_key(|s| s.word.start);
+        if synonyms_a == self.synonyms_a && synonyms_b == self.synonyms_b {
+            return;
+        }
+        self.synonyms_a = synonyms_a;
+        self.synonyms_b = synonyms_b;
+        self.tokens = None;
+        self.ngrams = None;
+    }
+
+    fn tokenize(&self) -> Result<Tokenized, Error> {
+        let (file_a, file_b) = (&self.file_a, &self.file_b);
+        let (synonyms_a, synonyms_b) = match &self.offsets {
+            Some((offsets_a, offsets_b)) => {
+                // Check them where the caller can make sense of the offsets in the error
+                synonyms::validate_synonyms(&self.synonyms_a, offsets_a.original_len(), offsets_b.original_len())?;
+                synonyms::validate_synonyms(&self.synonyms_b, offsets_b.original_len(), offsets_a.original_len())?;
+                synonyms::validate_linked_words(&self.synonyms_a, &self.synonyms_b)?;
+                synonyms::validate_linked_words(&self.synonyms_b, &self.synonyms_a)?;
+                (
+                    to_normalized(&self.synonyms_a, offsets_a, offsets_b),
+                    to_normalized(&self.synonyms_b, offsets_b, offsets_a),
+                )
+            }
+            None => (self.synonyms_a.clone(), self.synonyms_b.clone()),
+        };
+        synonyms::validate_synonyms(&synonyms_a, file_a.len(), file_b.len())?;
+        synonyms::validate_synonyms(&synonyms_b, file_b.len(), file_a.len())?;
+        synonyms::validate_linked_words(&synonyms_a, &synonyms_b)?;
+        synonyms::validate_linked_words(&synonyms_b, &synonyms_a)?;
+        let tokenizer = match &self.tokenizer {
+            Some(tokenizer) => tokenizer.as_ref(),
+            None => self.config.tokenization.tokenizer(),
+        };
+        let variants = VariantTable::new(&self.config.variants);
+        let dictionary = Dictionary::new(&self.config.dictionary, &self.config.normalization, &variants);
+        let occurrences_a = find_occurrences(tokenizer, &dictionary, file_a, &synonyms_a);
+        let occurrences_b = find_occurrences(tokenizer, &dictionary, file_b, &synonyms_b);
+        // The words a synonym of one text links to have to be tokens of the other text as well,
+        // and take precedence over the dictionary occurrences they overlap
+        let linked_words = |synonyms: &[Synonym]| synonyms.iter().flat_map(|s| s.synonyms.clone()).collect::<Vec<_>>();
+        let occurrence_words = |occurrences: &[(Word, usize)]| occurrences.iter().map(|&(word, _)| word).collect::<Vec<_>>();
+        let words_a = with_words(&with_words(&synonyms_a, linked_words(&synonyms_b)), occurrence_words(&occurrences_a));
+        let words_b = with_words(&with_words(&synonyms_b, linked_words(&synonyms_a)), occurrence_words(&occurrences_b));
+
+        let mut vocabulary = Vocabulary::with_variants(&variants);
+        let (linked_a, linked_b, substitutions) = synonyms::resolve_synonyms(
+            file_a,
+            file_b,
+            &synonyms_a,
+            &synonyms_b,
+            &occurrences_a,
+            &occurrences_b,
+            dictionary.links(),
+            &mut vocabulary,
+        );
+        let token_a = tokenizer::tokenize(tokenizer, file_a, &words_a, &linked_a, &mut vocabulary);
+        let token_b = tokenizer::tokenize(tokenizer, file_b, &words_b, &linked_b, &mut vocabulary);
+        let texts = [(&file_a[..], &token_a[..]), (&file_b[..], &token_b[..])];
+        let costs = edit_costs(&self.config, substitutions, &texts, &variants);
+        let classes = synonyms::synonym_classes(&linked_a, &linked_b);
+        let homophones = costs.merge_homophones(&token_a).zip(costs.merge_homophones(&token_b));
+        Ok(Tokenized {
+            token_a,
+            token_b,
+            homophones,
+            classes,
+            costs,
+        })
+    }
+
+    pub fn run(&mut self, monitor: &mut Monitor) -> Result<ResponseAndOverall, Error> {
+        let config = &self.config;
+        config.validate()?;
+        if config.self_comparison != SelfComparison::Off && self.str_a != self.str_b {
+            return Err(Error::InvalidParameter {
+                name: "selfComparison",
+                reason: "A and B have to be the same text".to_string(),
+            });
+        }
+        if self.tokens.is_none() {
+            self.tokens = Some(self.tokenize()?);
+        }
+        let Tokenized {
+            token_a,
+            token_b,
+            homophones,
+            classes,
+            costs,
+        } = self.tokens.as_ref().unwrap();
+        for (text, tokens) in [("A", token_a), ("B", token_b)] {
+            check_not_empty(text, tokens)?;
+        }
+        // The matches are found with the homophones merged, but told apart in the response
+        let (match_a, match_b) = match homophones {
+            Some((a, b)) => (a, b),
+            None => (token_a, token_b),
+        };
+        let ngrams = match config.algorithm {
+            Algorithm::Comparativus => {
+                if self.ngrams.is_none() {
+                    self.ngrams = Some(SharedNgrams::new(match_a, match_b, config.kernel_size)?);
+                }
+                self.ngrams.as_ref()
+            }
+            _ => None,
+        };
+        // The search is the first half of the work, the overall similarity the second
+        let matches = find_matches(match_a, match_b, ngrams, costs, config, &mut monitor.part(0, 2, 0))?;
+        let mut overall = monitor.part(1, 2, matches.results.len());
+        let mut response =
+            crate::build_response(&self.file_a, &self.file_b, token_a, token_b, costs, matches, config, &mut overall)?;
+        response.synonym_classes = classes.clone();
+        if let Some((offsets_a, offsets_b)) = self.offsets.as_ref().filter(|_| config.original_offsets) {
+            let clusters = self.tokenizer.is_none() && config.tokenization == Tokenization::Grapheme;
+            to_original(&mut response, (&self.str_a, offsets_a), (&self.str_b, offsets_b), clusters);
+        }
+        Ok(response)
+    }
+}
+
+pub(crate) fn check_not_empty(text: impl Into<Cow<'static, str>>, tokens: &[Token]) -> Result<(), Error> {
+    if tokens.is_empty() {
+        return Err(Error::TextTooShort {
+            text: text.into(),
+            length: 0,
+            required: 1,
+        });
+    }
+    Ok(())
+}
+
+// The dictionary occurrences in a text, see `Dictionary::find`
+pub(crate) fn find_occurrences(
+    tokenizer: &dyn Tokenizer,
+    dictionary: &Dictionary,
+    text: &[char],
+    synonyms: &[Synonym],
+) -> Vec<(Word, usize)> {
+    if dictionary.is_empty() {
+        return Vec::new();
+    }
+    // The plain tokens tell where the dictionary forms may start and end
+    let tokens = tokenizer::tokenize(tokenizer, text, synonyms, &LinkedWords::default(), &mut Vocabulary::default());
+    dictionary.find(text, &tokens, synonyms)
+}
+
+// The edit costs of the config, given the substitutions between synonym classes and the texts
+pub(crate) fn edit_costs(
+    config: &AnalysisConfig,
+    substitutions: Vec<(u32, u32, f32)>,
+    texts: &[(&[char], &[Token])],
+    variants: &VariantTable,
+) -> EditCosts {
+    let char_substitutions = config.costs.char_substitutions(texts, variants);
+    let costs = EditCosts::new(&config.costs, substitutions.into_iter().chain(char_substitutions))
+        .with_phonetic(config.phonetic.char_substitutions(texts, variants));
+    match config.self_comparison {
+        SelfComparison::Off => costs,
+        _ => costs.for_same_text(),
+    }
+}
+
+// Runs the algorithm of the config, Comparativus from the shared n-grams if there are any
+pub(crate) fn find_matches(
+    token_a: &[Token],
+    token_b: &[Token],
+    ngrams: Option<&SharedNgrams>,
+    costs: &EditCosts,
+    config: &AnalysisConfig,
+    monitor: &mut Monitor,
+) -> Result<Matches, Error> {
+    let matches = match (config.algorithm, ngrams) {
+        (Algorithm::Comparativus, Some(ngrams)) => {
+            comparativus::find_matches_from_ngrams(token_a, token_b, ngrams, costs, config, monitor)?
+        }
+        (Algorithm::Comparativus, None) => comparativus::find_levenshtein_matches(token_a, token_b, costs, config, monitor)?,
+        (Algorithm::SmithWaterman, _) => smith_waterman::find_local_alignments(token_a, token_b, costs, config, monitor)?,
+        (Algorithm::GreedyStringTiling, _) => greedy_string_tiling::find_tiles(token_a, token_b, config, monitor)?,
+        (Algorithm::SuffixArray, _) => suffix_array::find_exact_repeats(token_a, token_b, costs, config, monitor)?,
+    };
+    monitor.report(1.0, matches.results.len())?;
+    Ok(matches)
+}
+
+// Moves the results and synonym classes from the normalized texts to the texts as given
+pub(crate) fn to_original(
+    response: &mut ResponseAndOverall,
+    (str_a, offsets_a): (&str, &OffsetMap),
+    (str_b, offsets_b): (&str, &OffsetMap),
+    clusters: bool,
+) {
+    for result in response.result.iter_mut() {
+        result.a = offsets_a.to_original(result.a);
+        result.b = offsets_b.to_original(result.b);
+    }
+    for class in response.synonym_classes.iter_mut() {
+        class.a.iter_mut().for_each(|word| *word = offsets_a.to_original(*word));
+        class.b.iter_mut().for_each(|word| *word = offsets_b.to_original(*word));
+    }
+    // Dropping chars can leave part of a cluster out of the normalized text
+    if clusters {
+        let boundaries_a = tokenizer::grapheme_boundaries(&str_a.chars().collect::<Vec<_>>());
+        let boundaries_b = tokenizer::grapheme_boundaries(&str_b.chars().collect::<Vec<_>>());
+        for result in response.result.iter_mut() {
+            result.a = widen_to_clusters(result.a, &boundaries_a);
+            result.b = widen_to_clusters(result.b, &boundaries_b);
+        }
+    }
+}
+
+// A synonym's own word is in its text, the words it links to are in the other text.
+// Words made up of dropped chars only are gone from the normalized text, so they are dropped too.
+fn to_normalized(synonyms: &[Synonym], offsets: &OffsetMap, other_offsets: &OffsetMap) -> Vec<Synonym> {
+    let word = |offsets: &OffsetMap, word: &Word| {
+        let normalized = Word {
+            start: offsets.to_normalized(word.start),
+            end: offsets.to_normalized(word.end),
+            cost: word.cost,
+        };
+        (normalized.start < normalized.end).then_some(normalized)
+    };
+    synonyms
+        .iter()
+        .filter_map(|synonym| {
+            Some(Synonym {
+                word: word(offsets, &synonym.word)?,
+                synonyms: synonym.synonyms.iter().filter_map(|w| word(other_offsets, w)).collect(),
+            })
+        })
+        .collect()
+}
+
+// The words that become tokens of their own: the synonym words, and the other words, such
+// as dictionary occurrences, that overlap none of them or of each other
+pub(crate) fn with_words(synonyms: &[Synonym], words: impl IntoIterator<Item = Word>) -> Vec<Synonym> {
+    let mut words: Vec<Word> = words.into_iter().collect();
+    if words.is_empty() {
+        return synonyms.to_vec();
+    }
+    words.sort_unstable_by_key(|word| (word.start, word.end));
+    let mut merged = Vec::with_capacity(synonyms.len() + words.len());
+    let mut own = synonyms.iter().peekable();
+    let mut end = 0;
+    for word in words {
+        while let Some(synonym) = own.next_if(|s| s.word.start < word.end) {
+            end = synonym.word.end;
+            merged.push(synonym.clone());
+        }
+        if word.start >= end {
+            end = word.end;
+            merged.push(Synonym::new(word, Vec::new()));
+        }
+    }
+    merged.extend(own.cloned());
+    merged
+}
+
+fn widen_to_clusters(substring: Substring, boundaries: &[bool]) -> Substring {
+    let start = (0..=substring.start).rev().find(|&i| boundaries[i]).unwrap_or(0);
+    let end = (substring.end..boundaries.len()).find(|&i| boundaries[i]).unwrap_or(substring.end);
+    Substring { start, end }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dictionary::DictionaryGroup,
+        normalize::{CharClass, Normalization},
+    };
+
+    #[test]
+    fn linked_words_match_the_same_text() {
+        let a = "學而時習之不亦說乎有朋自遠方來";
+        let b = "學而時習之不亦說乎有朋自遠方悅";
+        let mut analyzer = Analyzer::new(a, b);
+        let plain = analyzer.run(&mut Monitor::default()).unwrap().overall_levenstein_similarity;
+        analyzer.set_synonyms(vec![Synonym::new(Word::new(7, 8), vec![Word::new(14, 15)])], Vec::new());
+        let linked = analyzer.run(&mut Monitor::default()).unwrap().overall_levenstein_similarity;
+        assert!((plain - 14.0 / 15.0).abs() < 1e-6);
+        assert_eq!(linked, plain);
+    }
+
+    // The levenshtein matches of a text with itself, as offsets in A and B
+    fn repeats(text: &str, algorithm: Algorithm, self_comparison: SelfComparison) -> Vec<(usize, usize, usize, usize)> {
+        let config = AnalysisConfig {
+            algorithm,
+            self_comparison,
+            ..AnalysisConfig::default()
+        };
+        let response = crate::analyze(text, text, Vec::new(), Vec::new(), &config).unwrap();
+        let matches = response.result.iter().filter(|x| x.levenshtein_match);
+        matches.map(|x| (x.a.start, x.a.end, x.b.start, x.b.end)).collect()
+    }
+
+    const ALGORITHMS: [Algorithm; 4] = [
+        Algorithm::Comparativus,
+        Algorithm::SmithWaterman,
+        Algorithm::GreedyStringTiling,
+        Algorithm::SuffixArray,
+    ];
+
+    #[test]
+    fn self_comparison_finds_repeats_off_the_diagonal() {
+        for algorithm in ALGORITHMS {
+            for self_comparison in [SelfComparison::Overlapping, SelfComparison::Disjoint] {
+                let found = repeats("abcdefghijklmabcdefghij", algorithm, self_comparison);
+                assert_eq!(found, [(0, 10, 13, 23)], "{:?} {:?}", algorithm, self_comparison);
+            }
+        }
+    }
+
+    #[test]
+    fn the_overall_similarity_of_a_text_with_itself_is_the_share_it_repeats() {
+        for algorithm in ALGORITHMS {
+            let config = AnalysisConfig {
+                algorithm,
+                self_comparison: SelfComparison::Disjoint,
+                ..AnalysisConfig::default()
+            };
+            let repeated = crate::analyze("abcdefghijklmabcdefghij", "abcdefghijklmabcdefghij", Vec::new(), Vec::new(), &config);
+            assert_eq!(repeated.unwrap().overall_levenstein_similarity, 20.0 / 23.0, "{:?}", algorithm);
+            let unrepeated = crate::analyze("abcdefghijklm", "abcdefghijklm", Vec::new(), Vec::new(), &config);
+            assert_eq!(unrepeated.unwrap().overall_levenstein_similarity, 0.0, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn disjoint_repeats_back_to_back_keep_their_first_token() {
+        let copies = "abcdefgh".repeat(3);
+        for algorithm in ALGORITHMS {
+            let disjoint = repeats(&copies, algorithm, SelfComparison::Disjoint);
+            // Each copy is found whole, starting on its first token right where the one before it ends
+            assert!(disjoint.contains(&(0, 8, 8, 16)), "{:?} {:?}", algorithm, disjoint);
+            let third = disjoint.iter().find(|x| (x.2, x.3) == (16, 24));
+            assert!(third.is_some_and(|x| x.0 % 8 == 0), "{:?} {:?}", algorithm, disjoint);
+        }
+        let smith_waterman = repeats(&copies, Algorithm::SmithWaterman, SelfComparison::Disjoint);
+        assert_eq!(smith_waterman, [(0, 8, 8, 16), (8, 16, 16, 24)]);
+    }
+
+    #[test]
+    fn only_overlapping_repeats_run_into_each_other() {
+        let refrain = "abababababababab";
+        for algorithm in ALGORITHMS {
+            let overlapping = repeats(refrain, algorithm, SelfComparison::Overlapping);
+            let disjoint = repeats(refrain, algorithm, SelfComparison::Disjoint);
+            // Every algorithm finds the refrain shifted by one repetition, and none pairs a token with itself
+            assert!(overlapping.contains(&(0, 14, 2, 16)), "{:?} {:?}", algorithm, overlapping);
+            assert!(overlapping.iter().all(|&(start_a, _, start_b, _)| start_a < start_b), "{:?}", algorithm);
+            // Apart, the two halves are the longest repeat
+            assert_eq!(disjoint, [(0, 8, 8, 16)], "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn offsets_are_in_the_texts_as_given_unless_turned_off() {
+        let a = "學而，時習之，不亦說乎？有朋自遠方來";
+        let b = "學而時習之不亦說乎有朋自遠方來";
+        let config = |original_offsets| AnalysisConfig {
+            normalization: Normalization {
+                drop: vec![CharClass::Classic],
+                ..Normalization::default()
+            },
+            original_offsets,
+            ..AnalysisConfig::default()
+        };
+        let spans = |response: &ResponseAndOverall| -> Vec<(usize, usize, usize, usize)> {
+            let matches = response.result.iter().filter(|x| x.levenshtein_match);
+            matches.map(|x| (x.a.start, x.a.end, x.b.start, x.b.end)).collect()
+        };
+        // The same analyzer switches between them without tokenizing again
+        let mut analyzer = Analyzer::new(a, b);
+        analyzer.set_config(config(true));
+        assert_eq!(spans(&analyzer.run(&mut Monitor::default()).unwrap()), [(0, 18, 0, 15)]);
+        analyzer.set_config(config(false));
+        assert_eq!(spans(&analyzer.run(&mut Monitor::default()).unwrap()), [(0, 15, 0, 15)]);
+        for (original_offsets, end_a) in [(true, 18), (false, 15)] {
+            let mut corpus = crate::corpus::Corpus::new(&[a, b]);
+            corpus.set_config(config(original_offsets));
+            let response = corpus.run(&mut Monitor::default()).unwrap();
+            assert_eq!(spans(&response.pairs[0].response), [(0, end_a, 0, 15)]);
+        }
+    }
+
+    #[test]
+    fn errors_tell_what_is_wrong() {
+        let config = AnalysisConfig::default();
+        let analyze = |a: &str, synonyms_a: Vec<Synonym>| crate::analyze(a, "學而時習之", synonyms_a, Vec::new(), &config);
+        let too_short = Error::TextTooShort {
+            text: "A".into(),
+            length: 2,
+            required: config.kernel_size,
+        };
+        assert_eq!(analyze("學而", Vec::new()).unwrap_err(), too_short);
+        let empty = Error::TextTooShort {
+            text: "A".into(),
+            length: 0,
+            required: 1,
+        };
+        assert_eq!(analyze("", Vec::new()).unwrap_err(), empty);
+        let outside = vec![Synonym::new(Word::new(3, 6), vec![Word::new(0, 1)])];
+        assert!(matches!(analyze("學而時習", outside).unwrap_err(), Error::MalformedSynonym { .. }));
+        let overlapping = vec![
+            Synonym::new(Word::new(0, 2), vec![Word::new(0, 1)]),
+            Synonym::new(Word::new(1, 3), vec![Word::new(2, 3)]),
+        ];
+        assert!(matches!(analyze("學而時習", overlapping).unwrap_err(), Error::MalformedSynonym { .. }));
+    }
+
+    #[test]
+    fn cached_runs_equal_fresh_ones() {
+        let a = "學而時習之，不亦說乎？有朋自遠方來，不亦樂乎？人不知而不慍，不亦君子乎？";
+        let b = "學而時習之，不亦悅乎！有朋自遠方來，不亦樂乎？人不知而不慍，不亦君子乎";
+        let mut analyzer = Analyzer::new(a, b);
+        let mut synonyms = (Vec::new(), Vec::new());
+        let check = |analyzer: &mut Analyzer, synonyms: &(Vec<Synonym>, Vec<Synonym>)| {
+            let cached = analyzer.run(&mut Monitor::default()).unwrap();
+            let fresh = crate::analyze(a, b, synonyms.0.clone(), synonyms.1.clone(), &analyzer.config).unwrap();
+            assert_eq!(serde_json::to_string(&cached).unwrap(), serde_json::to_string(&fresh).unwrap());
+        };
+        check(&mut analyzer, &synonyms);
+
+        // The ratio only changes the search, so the tokens and n-grams are kept
+        analyzer.set_config(AnalysisConfig {
+            ratio: 0.9,
+            ..analyzer.config.clone()
+        });
+        assert!(analyzer.tokens.is_some() && analyzer.ngrams.is_some());
+        check(&mut analyzer, &synonyms);
+
+        analyzer.set_config(AnalysisConfig {
+            kernel_size: 3,
+            ..analyzer.config.clone()
+        });
+        assert!(analyzer.tokens.is_some() && analyzer.ngrams.is_none());
+        check(&mut analyzer, &synonyms);
+
+        analyzer.set_config(AnalysisConfig {
+            variants: vec!["說悅".to_string()],
+            ..analyzer.config.clone()
+        });
+        assert!(analyzer.tokens.is_none() && analyzer.ngrams.is_none());
+        check(&mut analyzer, &synonyms);
+
+        analyzer.set_config(AnalysisConfig {
+            normalization: Normalization {
+                drop: vec![CharClass::Punctuation],
+                ..Normalization::default()
+            },
+            ..analyzer.config.clone()
+        });
+        assert!(analyzer.tokens.is_none() && analyzer.offsets.is_some());
+        check(&mut analyzer, &synonyms);
+
+        // The same synonyms in another order change nothing
+        synonyms.0 = vec![
+            Synonym::new(Word::new(14, 15), vec![Word::new(12, 13)]),
+            Synonym::new(Word::new(7, 8), vec![Word::new(7, 8)]),
+        ];
+        analyzer.set_synonyms(synonyms.0.clone(), Vec::new());
+        assert!(analyzer.tokens.is_none());
+        check(&mut analyzer, &synonyms);
+        analyzer.set_synonyms(synonyms.0.iter().rev().cloned().collect(), Vec::new());
+        assert!(analyzer.tokens.is_some() && analyzer.ngrams.is_some());
+        check(&mut analyzer, &synonyms);
+    }
+
+    #[test]
+    fn words_linked_from_the_other_text_become_tokens() {
+        let synonyms = [Synonym::new(Word::new(2, 4), Vec::new())];
+        let words = with_words(&synonyms, [Word::new(5, 7), Word::new(3, 5), Word::new(0, 1)]);
+        let spans: Vec<_> = words.iter().map(|s| (s.word.start, s.word.end)).collect();
+        // The word overlapping one of the text's own synonyms is left out
+        assert_eq!(spans, [(0, 1), (2, 4), (5, 7)]);
+    }
+
+    #[test]
+    fn weighted_links_give_partial_credit() {
+        let a = "學而時習之不亦說乎有朋自遠方來";
+        let b = "學而時習之不亦悅乎有朋自遠方來";
+        let similarity = |synonyms_a: Vec<Synonym>, dictionary: Vec<DictionaryGroup>| {
+            let config = AnalysisConfig {
+                dictionary,
+                ..AnalysisConfig::default()
+            };
+            let response = crate::analyze(a, b, synonyms_a, Vec::new(), &config).unwrap();
+            let matched = response.result.iter().find(|x| x.levenshtein_match).unwrap();
+            assert_eq!(matched.similarity, response.overall_levenstein_similarity);
+            matched.similarity
+        };
+        let link = |cost: Option<f32>| {
+            let word = Word::new(7, 8);
+            vec![Synonym::new(Word::new(7, 8), vec![cost.map_or(word, |cost| word.with_cost(cost))])]
+        };
+        let group = |cost: f32| {
+            vec![DictionaryGroup {
+                forms: vec!["說".to_string(), "悅".to_string()],
+                cost,
+            }]
+        };
+        assert_eq!(similarity(Vec::new(), Vec::new()), 14.0 / 15.0);
+        assert_eq!(similarity(link(None), Vec::new()), 1.0);
+        assert_eq!(similarity(link(Some(0.25)), Vec::new()), 14.75 / 15.0);
+        assert_eq!(similarity(Vec::new(), group(0.0)), 1.0);
+        assert_eq!(similarity(Vec::new(), group(0.5)), 14.5 / 15.0);
+        // A link and a group between the same words cost the lower of the two
+        assert_eq!(similarity(link(Some(0.25)), group(0.5)), 14.75 / 15.0);
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    borrow::Cow,
+    cmp::{max, min},
+};
+
+/*
+* This algorithm is equivalent to the algorithm at https://github.com/MGelein/comparativus
+*/
+use crate::{SelfComparison, config::AnalysisConfig, costs::EditCosts, error::Error, progress::Monitor, synonyms::Token, utils};
+use rustc_hash::{FxBuildHasher, FxHashMap};
+
+use crate::utils::Matches;
+
+struct Ngrams<'a> {
+    ngrams: FxHashMap<&'a [Token], Vec<usize>>,
+    keys: Vec<&'a [Token]>,
+}
+impl<'a> Ngrams<'a> {
+    fn new(size: usize) -> Self {
+        Ngrams {
+            ngrams: FxHashMap::with_capacity_and_hasher(size, FxBuildHasher),
+            keys: Vec::with_capacity(size),
+        }
+    }
+    fn add_gram(&mut self, gram: &'a [Token], index: usize) {
+        if let Some(v) = self.ngrams.get_mut(gram) {
+            v.push(index);
+        } else {
+            self.ngrams.insert(gram, vec![index]);
+            self.keys.push(gram);
+        }
+    }
+    fn get(&self, gram: &'a [Token]) -> Option<&Vec<usize>> {
+        self.ngrams.get(gram)
+    }
+}
+
+fn build_ngrams<'a>(text: &'a [Token], kernel_size: usize) -> Ngrams<'a> {
+    let mut ngrams: Ngrams = Ngrams::new(text.len() - kernel_size);
+    text.windows(kernel_size).enumerate().for_each(|(i, gram)| {
+        ngrams.add_gram(gram, i);
+    });
+    ngrams
+}
+
+// How many runs of shared n-grams start among the pairs of occurrences from row i, column j
+// on. A pair whose tokens before it are equal as well is in the run of the pair one token
+// back, which would have grown into the same match.
+fn unexamined_runs(occ_a: &[usize], occ_b: &[usize], i: usize, j: usize, text_a: &[Token], text_b: &[Token]) -> usize {
+    let before = |text: &[Token], x: usize| x.checked_sub(1).map(|x| text[x].key);
+    let starts_run = |x: usize, y: usize| before(text_a, x).is_none() || before(text_a, x) != before(text_b, y);
+    let mut runs = occ_b[j..].iter().filter(|&&y| starts_run(occ_a[i], y)).count();
+    // The later rows are counted by the tokens before the occurrences, so as not to walk every pair
+    let mut keys_b: FxHashMap<u32, usize> = FxHashMap::default();
+    for key in occ_b.iter().filter_map(|&y| before(text_b, y)) {
+        *keys_b.entry(key).or_insert(0) += 1;
+    }
+    for &x in &occ_a[i + 1..] {
+        let continued = before(text_a, x).and_then(|key| keys_b.get(&key)).copied().unwrap_or(0);
+        runs += occ_b.len() - continued;
+    }
+    runs
+}
+
+#[allow(clippy::too_many_arguments)]
+fn expand_all_matches(
+    occ_a: &[usize],
+    occ_b: &[usize],
+    text_a: &[Token],
+    text_b: &[Token],
+    costs: &EditCosts,
+    results: &mut Vec<utils::SubstringResult>,
+    min_ratio: f32,
+    max_strike: usize,
+    max_substrings: usize,
+    base_match_size: usize,
+    min_len: usize,
+    self_comparison: SelfComparison,
+    monitor: &Monitor,
+) -> Result<usize, Error> {
+    for (i, occurance_a) in occ_a.iter().enumerate() {
+        'nextMatch: for (j, occurance_b) in occ_b.iter().enumerate() {
+            if results.len() >= max_substrings {
+                // Every pair from here on is left unexamined
+                return Ok(unexamined_runs(occ_a, occ_b, i, j, text_a, text_b));
+            }
+            monitor.check()?;
+            let Some(max_len) = self_comparison.max_len(*occurance_a, *occurance_b) else {
+                continue;
+            };
+            let base_match_size = base_match_size.min(max_len);
+            for ma in results.iter() {
+                // Should we allow equality here?
+                if *occurance_a < ma.end_a
+                    && *occurance_a > ma.start_a
+                    && *occurance_b < ma.end_b
+                    && *occurance_b > ma.start_b
+                {
+                    // This match is embedded within an existing match, so we can safely skip
+                    continue 'nextMatch;
+                }
+            }
+            let mut ma = utils::SubstringResult {
+                start_a: *occurance_a,
+                end_a: min(*occurance_a + base_match_size, text_a.len()),
+                start_b: *occurance_b,
+                end_b: min(*occurance_b + base_match_size, text_b.len()),
+                len: base_match_size,
+                edit_ratio: 1.0,
+            };
+
+            let len = utils::find_length_from_tokens(&text_a[ma.start_a..ma.end_a], &text_b[ma.start_b..ma.end_b]);
+            
+            ma.len = len; // This may not necessarily be the same as base_match_size
+            ma.edit_ratio = utils::recompute_ratio(
+                // This is the ratio of the match, which has been set as 1.0 before, but we need the real value
+                text_a, text_b, costs, ma.start_a, ma.end_a, ma.start_b, ma.end_b, ma.len,
+            );
+            while ma.start_a < ma.end_a && ma.start_b < ma.end_b && ma.edit_ratio < min_ratio {
+                ma.len -= max(text_a[ma.end_a-1].len(), text_b[ma.end_b-1].len());
+                ma.end_a -= 1;
+                ma.end_b -= 1;
+                ma.edit_ratio = utils::recompute_ratio(
+                    text_a, text_b, costs, ma.start_a, ma.end_a, ma.start_b, ma.end_b, ma.len,
+                );
+            }
+            if self_comparison != SelfComparison::Off && (ma.start_a == ma.end_a || ma.start_b == ma.end_b) {
+                // Nothing of a seed cut short by self-comparison is left to expand
+                continue;
+            }
+            utils::expand_match_left_and_right(
+                &mut ma,
+                text_a,
+                text_b,
+                costs,
+                min_ratio,
+                max_strike,
+                self_comparison,
+                monitor.cancellation(),
+            );
+            if ma.len >= min_len {
+                results.push(ma)
+            };
+        }
+    }
+    Ok(0)
+}
+
+// The n-grams of one text. A text compared with several others only needs one.
+pub struct NgramIndex<'a> {
+    kernel_size: usize,
+    ngrams: Ngrams<'a>,
+}
+
+impl<'a> NgramIndex<'a> {
+    pub fn new(text: impl Into<Cow<'static, str>>, tokens: &'a [Token], kernel_size: usize) -> Result<Self, Error> {
+        if tokens.len() < kernel_size {
+            return Err(Error::TextTooShort {
+                text: text.into(),
+                length: tokens.len(),
+                required: kernel_size,
+            });
+        }
+        Ok(NgramIndex {
+            kernel_size,
+            ngrams: build_ngrams(tokens, kernel_size),
+        })
+    }
+}
+
+// The occurrences of every n-gram found in both texts, in order of first occurrence in A.
+// It only depends on the tokens and the kernel size, so sessions keep it between runs.
+pub struct SharedNgrams {
+    pub kernel_size: usize,
+    occurrences: Vec<(Vec<usize>, Vec<usize>)>,
+}
+
+impl SharedNgrams {
+    pub fn new(a: &[Token], b: &[Token], kernel_size: usize) -> Result<Self, Error> {
+        let ngrams_a = NgramIndex::new("A", a, kernel_size)?;
+        let ngrams_b = NgramIndex::new("B", b, kernel_size)?;
+        Ok(SharedNgrams::between(&ngrams_a, &ngrams_b))
+    }
+
+    // Both indexes have to be built with the same kernel size
+    pub fn between<'a>(a: &NgramIndex<'a>, b: &NgramIndex<'a>) -> Self {
+        let occurrences = a
+            .ngrams
+            .keys
+            .iter()
+            .filter_map(|&gram| Some((a.ngrams.get(gram)?.clone(), b.ngrams.get(gram)?.clone())))
+            .collect();
+        SharedNgrams {
+            kernel_size: a.kernel_size,
+            occurrences,
+        }
+    }
+}
+
+pub fn find_levenshtein_matches(
+    a: &[Token],
+    b: &[Token],
+    costs: &EditCosts,
+    config: &AnalysisConfig,
+    monitor: &mut Monitor,
+) -> Result<Matches, Error> {
+    let ngrams = SharedNgrams::new(a, b, config.kernel_size)?;
+    find_matches_from_ngrams(a, b, &ngrams, costs, config, monitor)
+}
+
+pub fn find_matches_from_ngrams(
+    a: &[Token],
+    b: &[Token],
+    ngrams: &SharedNgrams,
+    costs: &EditCosts,
+    config: &AnalysisConfig,
+    monitor: &mut Monitor,
+) -> Result<Matches, Error> {
+    let mut ret: Vec<utils::SubstringResult> = Vec::new();
+    let mut dropped = 0;
+    for (i, (occ_a, occ_b)) in ngrams.occurrences.iter().enumerate() {
+        monitor.report(i as f32 / ngrams.occurrences.len() as f32, ret.len())?;
+        dropped += expand_all_matches(
+            occ_a,
+            occ_b,
+            a,
+            b,
+            costs,
+            &mut ret,
+            config.ratio,
+            config.max_strikes,
+            config.max_substrings,
+            config.base_match_size,
+            config.min_length,
+            config.self_comparison,
+            monitor,
+        )?;
+    }
+    ret.sort_unstable_by_key(|x| x.start_a);
+    Ok(Matches { results: ret, dropped })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnalysisConfig, Warning};
+
+    #[test]
+    fn the_limit_counts_the_matches_left_unfound() {
+        // Three passages apart from each other, with nothing in between that could join them
+        let (first, second, third) = ("天地玄黃宇宙洪荒日月盈昃", "辰宿列張寒來暑往秋收冬藏", "閏餘成歲律呂調陽雲騰致雨");
+        let a = format!("{}甲乙丙丁戊{}己庚辛壬癸{}", first, second, third);
+        let b = format!("{}子丑寅卯辰{}巳午未申酉{}", first, second, third);
+        let analyze = |max_substrings| {
+            let config = AnalysisConfig {
+                max_substrings,
+                ..AnalysisConfig::default()
+            };
+            crate::analyze(&a, &b, Vec::new(), Vec::new(), &config).unwrap()
+        };
+        let all = analyze(3);
+        assert_eq!(all.result.iter().filter(|x| x.levenshtein_match).count(), 3);
+        assert!(all.warnings.is_empty() && !all.truncated);
+        // The n-grams later in the first passage belong to its match, the other two passages are lost
+        let limited = analyze(1);
+        assert_eq!(limited.result.iter().filter(|x| x.levenshtein_match).count(), 1);
+        assert_eq!(limited.warnings, [Warning::MaxSubstringsReached { limit: 1, dropped: 2 }]);
+        assert!(limited.truncated);
+    }
+}
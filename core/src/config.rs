@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Algorithm, SelfComparison, Tokenization, costs::CostModel, dictionary::DictionaryGroup, error::Error,
+    normalize::Normalization, phonetic::Phonetic, smith_waterman::Scoring,
+};
+
+// Bump this whenever a field changes meaning, so that old callers are rejected
+// instead of silently getting different results
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AnalysisConfig {
+    pub version: u32,
+    pub algorithm: Algorithm,
+    pub tokenization: Tokenization,
+    pub min_length: usize,
+    pub ratio: f32,
+    pub max_strikes: usize,
+    pub max_substrings: usize,
+    pub kernel_size: usize,
+    pub base_match_size: usize,
+    // Whether the exact matches of the suffix array grow through edits as far as the ratio and
+    // strikes allow, as the seeds of Comparativus do. It costs far more than finding them.
+    pub extend_repeats: bool,
+    pub scoring: Scoring,
+    // Applied to both texts before the analysis, synonyms and results stay in offsets of the texts as given
+    pub normalization: Normalization,
+    // Whether the results and synonym classes are moved back to offsets of the texts as given,
+    // or left in offsets of the normalized texts. Synonyms are given in offsets of the texts as given either way.
+    pub original_offsets: bool,
+    // Groups of characters that count as the same character, see `variants::parse_variant_groups`
+    pub variants: Vec<String>,
+    // Groups of word forms that match each other anywhere in both texts, see `dictionary::parse_dictionary`
+    pub dictionary: Vec<DictionaryGroup>,
+    // What the edits cost in the edit ratios, see `costs::CostModel`
+    pub costs: CostModel,
+    // Characters that sound alike and substitute for each other cheaply, see `phonetic::Phonetic`
+    pub phonetic: Phonetic,
+    // Whether A and B are the same text, searched for repeated passages
+    pub self_comparison: SelfComparison,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        AnalysisConfig {
+            version: CONFIG_VERSION,
+            algorithm: Algorithm::Comparativus,
+            tokenization: Tokenization::Character,
+            min_length: 7,
+            ratio: 0.8,
+            max_strikes: 3,
+            max_substrings: 20000,
+            kernel_size: 4,
+            base_match_size: 10,
+            extend_repeats: false,
+            scoring: Scoring::default(),
+            normalization: Normalization::default(),
+            original_offsets: true,
+            variants: Vec::new(),
+            dictionary: Vec::new(),
+            costs: CostModel::default(),
+            phonetic: Phonetic::default(),
+            self_comparison: SelfComparison::Off,
+        }
+    }
+}
+
+impl AnalysisConfig {
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |name, reason: String| Err(Error::InvalidParameter { name, reason });
+        if self.version == 0 || self.version > CONFIG_VERSION {
+            return invalid(
+                "version",
+                format!("{} is not supported, the latest version is {}", self.version, CONFIG_VERSION),
+            );
+        }
+        if !(0.0..=1.0).contains(&self.ratio) {
+            return invalid("ratio", format!("{} is not between 0 and 1", self.ratio));
+        }
+        for (name, value) in [("kernelSize", self.kernel_size), ("baseMatchSize", self.base_match_size)] {
+            if value == 0 {
+                return invalid(name, "must be at least 1".to_string());
+            }
+        }
+        if self.scoring.match_score <= 0 {
+            return invalid("scoring.matchScore", "must be positive".to_string());
+        }
+        // A positive penalty would let alignments grow over anything and never end
+        let penalties = [
+            ("scoring.mismatchScore", self.scoring.mismatch_score),
+            ("scoring.gapScore", self.scoring.gap_score),
+        ];
+        for (name, value) in penalties {
+            if value > 0 {
+                return invalid(name, format!("{} is positive, it must be at most 0", value));
+            }
+        }
+        let costs = &self.costs;
+        let edits = [
+            ("costs.insertion", Some(costs.insertion)),
+            ("costs.deletion", Some(costs.deletion)),
+            ("costs.substitution", Some(costs.substitution)),
+            ("costs.transposition", costs.transposition),
+        ];
+        let groups = costs.substitutions.iter().map(|group| ("costs.substitutions", Some(group.cost)));
+        let phonetic = [("phonetic.cost", Some(self.phonetic.cost))];
+        for (name, cost) in edits.into_iter().chain(groups).chain(phonetic) {
+            if let Some(cost) = cost.filter(|cost| !(cost.is_finite() && *cost >= 0.0)) {
+                return invalid(name, format!("{} is not a finite number of at least 0", cost));
+            }
+        }
+        if let Some(group) = self.dictionary.iter().find(|group| !(0.0..=1.0).contains(&group.cost)) {
+            return invalid(
+                "dictionary",
+                format!("the cost of {} is {}, which is not between 0 and 1", group.forms.join("/"), group.cost),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_take_their_defaults() {
+        let config: AnalysisConfig = serde_json::from_str(r#"{"minLength": 12, "scoring": {"gapScore": -3}}"#).unwrap();
+        assert_eq!(config.min_length, 12);
+        assert_eq!(config.scoring.gap_score, -3);
+        assert_eq!(config.scoring.match_score, Scoring::default().match_score);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.ratio, AnalysisConfig::default().ratio);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_versions_and_out_of_range_values() {
+        let name = |config: AnalysisConfig| match config.validate() {
+            Err(Error::InvalidParameter { name, .. }) => name,
+            other => panic!("{:?}", other),
+        };
+        let default = AnalysisConfig::default;
+        assert_eq!(name(AnalysisConfig { version: 0, ..default() }), "version");
+        assert_eq!(name(AnalysisConfig { version: CONFIG_VERSION + 1, ..default() }), "version");
+        assert_eq!(name(AnalysisConfig { ratio: 1.5, ..default() }), "ratio");
+        assert_eq!(name(AnalysisConfig { kernel_size: 0, ..default() }), "kernelSize");
+        let scoring = Scoring::new(2, 1, -1);
+        assert_eq!(name(AnalysisConfig { scoring, ..default() }), "scoring.mismatchScore");
+        let costs = CostModel {
+            transposition: Some(f32::NAN),
+            ..CostModel::default()
+        };
+        assert_eq!(name(AnalysisConfig { costs, ..default() }), "costs.transposition");
+    }
+}
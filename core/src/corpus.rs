@@ -0,0 +1,237 @@
+use serde::Serialize;
+
+use crate::{
+    Algorithm, ResponseAndOverall, SelfComparison, Tokenization, analyzer,
+    comparativus::{NgramIndex, SharedNgrams},
+    config::AnalysisConfig,
+    dictionary::Dictionary,
+    error::Error,
+    normalize::OffsetMap,
+    progress::Monitor,
+    synonyms::{self, Synonym, Token, Vocabulary},
+    tokenizer::{self, Tokenizer},
+    variants::VariantTable,
+};
+
+// The comparison of two texts of a corpus, A being the one with the lower index
+#[derive(Serialize, Debug)]
+pub struct CorpusPair {
+    pub a: usize,
+    pub b: usize,
+    #[serde(flatten)]
+    pub response: ResponseAndOverall,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CorpusResponse {
+    // Row i, column j compares text i with text j, the diagonal is 1
+    pub overall_levenstein_similarity: Vec<Vec<f32>>,
+    pub overall_cosine_similarity: Vec<Vec<f32>>,
+    // Every pair of texts once, ordered by A and then by B
+    pub pairs: Vec<CorpusPair>,
+}
+
+// Compares every text of a corpus with every other one, such as a base text with its witnesses.
+// Each text is normalized, tokenized and indexed once per run, against a vocabulary shared by
+// all of them. Positional synonyms only ever link two texts, so across a corpus words only
+// match through the variants and the dictionary of the config.
+pub struct Corpus {
+    texts: Vec<String>,
+    config: AnalysisConfig,
+    // Replaces the tokenizer picked by the config's tokenization
+    tokenizer: Option<Box<dyn Tokenizer>>,
+}
+
+// Texts are named by their index in errors
+fn name(text: usize) -> String {
+    format!("#{}", text)
+}
+
+impl Corpus {
+    pub fn new<S: AsRef<str>>(texts: &[S]) -> Self {
+        Corpus {
+            texts: texts.iter().map(|text| text.as_ref().to_string()).collect(),
+            config: AnalysisConfig::default(),
+            tokenizer: None,
+        }
+    }
+
+    pub fn set_config(&mut self, config: AnalysisConfig) {
+        self.config = config;
+    }
+
+    pub fn set_tokenizer(&mut self, tokenizer: Box<dyn Tokenizer>) {
+        self.tokenizer = Some(tokenizer);
+    }
+
+    pub fn run(&self, monitor: &mut Monitor) -> Result<CorpusResponse, Error> {
+        let config = &self.config;
+        config.validate()?;
+        if config.self_comparison != SelfComparison::Off {
+            return Err(Error::InvalidParameter {
+                name: "selfComparison",
+                reason: "a corpus compares different texts with each other".to_string(),
+            });
+        }
+        let n = self.texts.len();
+        if n < 2 {
+            return Err(Error::InvalidParameter {
+                name: "texts",
+                reason: format!("{} given, at least 2 are needed", n),
+            });
+        }
+        let normalized: Vec<(Vec<char>, Option<OffsetMap>)> = self
+            .texts
+            .iter()
+            .map(|text| {
+                if config.normalization.is_identity() {
+                    (text.chars().collect(), None)
+                } else {
+                    let (normalized, offsets) = config.normalization.apply(text);
+                    (normalized.chars().collect(), Some(offsets))
+                }
+            })
+            .collect();
+        let tokenizer = match &self.tokenizer {
+            Some(tokenizer) => tokenizer.as_ref(),
+            None => config.tokenization.tokenizer(),
+        };
+        let variants = VariantTable::new(&config.variants);
+        let dictionary = Dictionary::new(&config.dictionary, &config.normalization, &variants);
+        let occurrences: Vec<_> = normalized
+            .iter()
+            .map(|(file, _)| analyzer::find_occurrences(tokenizer, &dictionary, file, &[]))
+            .collect();
+        let words: Vec<Vec<Synonym>> = occurrences
+            .iter()
+            .map(|occurrences| analyzer::with_words(&[], occurrences.iter().map(|&(word, _)| word)))
+            .collect();
+
+        let mut vocabulary = Vocabulary::with_variants(&variants);
+        let (linked, substitutions) = synonyms::resolve_occurrences(&occurrences, dictionary.links(), &mut vocabulary);
+        let mut tokens: Vec<Vec<Token>> = Vec::with_capacity(n);
+        for (i, (file, _)) in normalized.iter().enumerate() {
+            let text_tokens = tokenizer::tokenize(tokenizer, file, &words[i], &linked[i], &mut vocabulary);
+            analyzer::check_not_empty(name(i), &text_tokens)?;
+            tokens.push(text_tokens);
+        }
+        let texts: Vec<(&[char], &[Token])> = normalized
+            .iter()
+            .zip(&tokens)
+            .map(|((file, _), tokens)| (&file[..], &tokens[..]))
+            .collect();
+        let costs = analyzer::edit_costs(config, substitutions, &texts, &variants);
+        // The matches are found with the homophones merged, but told apart in the responses
+        let homophones: Vec<Option<Vec<Token>>> = tokens.iter().map(|tokens| costs.merge_homophones(tokens)).collect();
+        let matching: Vec<&[Token]> = tokens
+            .iter()
+            .zip(&homophones)
+            .map(|(tokens, homophones)| homophones.as_deref().unwrap_or(tokens))
+            .collect();
+        let indexes = match config.algorithm {
+            Algorithm::Comparativus => matching
+                .iter()
+                .enumerate()
+                .map(|(i, tokens)| NgramIndex::new(name(i), tokens, config.kernel_size))
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => Vec::new(),
+        };
+
+        let parts = n * (n - 1) / 2;
+        let clusters = self.tokenizer.is_none() && config.tokenization == Tokenization::Grapheme;
+        let mut levenshtein = vec![vec![1.0; n]; n];
+        let mut cosine = vec![vec![1.0; n]; n];
+        let mut pairs = Vec::with_capacity(parts);
+        let mut found = 0;
+        for a in 0..n {
+            for b in a + 1..n {
+                let ngrams = (!indexes.is_empty()).then(|| SharedNgrams::between(&indexes[a], &indexes[b]));
+                let mut part = monitor.part(pairs.len(), parts, found);
+                let matches = {
+                    let mut search = part.part(0, 2, 0);
+                    analyzer::find_matches(matching[a], matching[b], ngrams.as_ref(), &costs, config, &mut search)?
+                };
+                let mut overall = part.part(1, 2, matches.results.len());
+                found += matches.results.len();
+                let (file_a, offsets_a) = &normalized[a];
+                let (file_b, offsets_b) = &normalized[b];
+                let mut response =
+                    crate::build_response(file_a, file_b, &tokens[a], &tokens[b], &costs, matches, config, &mut overall)?;
+                response.synonym_classes = synonyms::synonym_classes(&linked[a], &linked[b]);
+                if let (Some(offsets_a), Some(offsets_b), true) = (offsets_a, offsets_b, config.original_offsets) {
+                    let original_a = (&self.texts[a][..], offsets_a);
+                    let original_b = (&self.texts[b][..], offsets_b);
+                    analyzer::to_original(&mut response, original_a, original_b, clusters);
+                }
+                levenshtein[a][b] = response.overall_levenstein_similarity;
+                levenshtein[b][a] = response.overall_levenstein_similarity;
+                cosine[a][b] = response.overall_cosine_similarity;
+                cosine[b][a] = response.overall_cosine_similarity;
+                pairs.push(CorpusPair { a, b, response });
+            }
+        }
+        Ok(CorpusResponse {
+            overall_levenstein_similarity: levenshtein,
+            overall_cosine_similarity: cosine,
+            pairs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dictionary::DictionaryGroup;
+
+    #[test]
+    fn pairs_equal_separate_runs() {
+        let texts = [
+            "學而時習之，不亦說乎？有朋自遠方來，不亦樂乎？人不知而不慍，不亦君子乎？",
+            "學而時習之，不亦悅乎？有朋自遠方来，不亦樂乎？子曰：人不知而不慍，不亦君子乎？",
+            "有朋自遠方來，不亦樂乎？人不知而不慍，不亦君子乎？曾子曰：吾日三省吾身。",
+            "曾子云：吾日三省吾身，為人謀而不忠乎？與朋友交而不信乎？傳不習乎？",
+        ];
+        for algorithm in [
+            Algorithm::Comparativus,
+            Algorithm::SmithWaterman,
+            Algorithm::GreedyStringTiling,
+            Algorithm::SuffixArray,
+        ] {
+            let config = AnalysisConfig {
+                algorithm,
+                variants: vec!["來来".to_string()],
+                dictionary: vec![
+                    DictionaryGroup {
+                        forms: vec!["曰".to_string(), "云".to_string()],
+                        cost: 0.0,
+                    },
+                    DictionaryGroup {
+                        forms: vec!["說".to_string(), "悅".to_string()],
+                        cost: 0.5,
+                    },
+                ],
+                ..AnalysisConfig::default()
+            };
+            let mut corpus = Corpus::new(&texts);
+            corpus.set_config(config.clone());
+            let response = corpus.run(&mut Monitor::default()).unwrap();
+            let order: Vec<(usize, usize)> = response.pairs.iter().map(|pair| (pair.a, pair.b)).collect();
+            assert_eq!(order, [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)]);
+            for pair in &response.pairs {
+                let separate = crate::analyze(texts[pair.a], texts[pair.b], Vec::new(), Vec::new(), &config).unwrap();
+                let json = |response: &ResponseAndOverall| serde_json::to_string(response).unwrap();
+                assert_eq!(json(&pair.response), json(&separate), "{:?} {} {}", algorithm, pair.a, pair.b);
+                let (a, b) = (pair.a, pair.b);
+                let levenshtein = &response.overall_levenstein_similarity;
+                assert_eq!(levenshtein[a][b], separate.overall_levenstein_similarity);
+                assert_eq!(levenshtein[b][a], levenshtein[a][b]);
+                let cosine = &response.overall_cosine_similarity;
+                assert_eq!((cosine[a][b], cosine[b][a]), (separate.overall_cosine_similarity, cosine[a][b]));
+            }
+            for i in 0..texts.len() {
+                assert_eq!(response.overall_levenstein_similarity[i][i], 1.0);
+                assert_eq!(response.overall_cosine_similarity[i][i], 1.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{synonyms::Token, variants::VariantTable};
+
+// Characters that substitute for each other at a lower cost, like glyphs that look alike
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SubstitutionGroup {
+    pub chars: String,
+    pub cost: f32,
+}
+
+// The cost of every edit in the edit distances. Insertions add a token of B, deletions drop
+// a token of A. Without a transposition cost, swapping two neighbours takes two edits.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CostModel {
+    pub insertion: f32,
+    pub deletion: f32,
+    pub substitution: f32,
+    pub transposition: Option<f32>,
+    pub substitutions: Vec<SubstitutionGroup>,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel {
+            insertion: 1.0,
+            deletion: 1.0,
+            substitution: 1.0,
+            transposition: None,
+            substitutions: Vec::new(),
+        }
+    }
+}
+
+impl CostModel {
+    // The substitutions between the single character tokens of the texts that the groups make cheaper
+    pub fn char_substitutions(&self, texts: &[(&[char], &[Token])], variants: &VariantTable) -> Vec<(u32, u32, f32)> {
+        char_substitutions(&self.substitutions, texts, variants)
+    }
+}
+
+// Links the keys of the single character tokens that fall in the same group, at its cost
+pub fn char_substitutions(
+    groups: &[SubstitutionGroup],
+    texts: &[(&[char], &[Token])],
+    variants: &VariantTable,
+) -> Vec<(u32, u32, f32)> {
+    if groups.is_empty() {
+        return Vec::new();
+    }
+    let mut members: FxHashMap<char, Vec<usize>> = FxHashMap::default();
+    for (i, group) in groups.iter().enumerate() {
+        for c in group.chars.chars() {
+            members.entry(variants.canonical(c)).or_default().push(i);
+        }
+    }
+    let mut keys: Vec<FxHashSet<u32>> = vec![FxHashSet::default(); groups.len()];
+    for &(text, tokens) in texts {
+        for token in tokens.iter().filter(|token| token.end - token.start == 1) {
+            for &i in members.get(&variants.canonical(text[token.start])).into_iter().flatten() {
+                keys[i].insert(token.key);
+            }
+        }
+    }
+    let mut substitutions = Vec::new();
+    for (group, keys) in groups.iter().zip(keys) {
+        let keys: Vec<u32> = keys.into_iter().collect();
+        for (i, &x) in keys.iter().enumerate() {
+            substitutions.extend(keys[i + 1..].iter().map(|&y| (x, y, group.cost)));
+        }
+    }
+    substitutions
+}
+
+// What it costs to edit one token sequence into another. Equal tokens cost nothing to
+// substitute, tokens linked with a weighted synonym or put in a substitution group the
+// lowest of their costs, and any other pair the substitution cost of the model. Homophones
+// are kept apart to tell the matches they made a difference to.
+#[derive(Clone, Debug)]
+pub struct EditCosts {
+    pub insertion: f32,
+    pub deletion: f32,
+    pub transposition: Option<f32>,
+    default_substitution: f32,
+    substitutions: FxHashMap<(u32, u32), f32>,
+    phonetic: FxHashMap<(u32, u32), f32>,
+    // Whether A and B are the same text
+    same_text: bool,
+}
+
+fn ordered(x: u32, y: u32) -> (u32, u32) {
+    if x <= y { (x, y) } else { (y, x) }
+}
+
+impl EditCosts {
+    // Keys linked more than once keep the lowest cost
+    pub fn new(model: &CostModel, substitutions: impl IntoIterator<Item = (u32, u32, f32)>) -> Self {
+        let mut costs = EditCosts {
+            insertion: model.insertion,
+            deletion: model.deletion,
+            transposition: model.transposition,
+            default_substitution: model.substitution,
+            substitutions: FxHashMap::default(),
+            phonetic: FxHashMap::default(),
+            same_text: false,
+        };
+        insert_lowest(&mut costs.substitutions, substitutions, model.substitution);
+        costs
+    }
+
+    // Adds the substitutions between homophones
+    pub fn with_phonetic(mut self, substitutions: impl IntoIterator<Item = (u32, u32, f32)>) -> Self {
+        insert_lowest(&mut self.phonetic, substitutions, self.default_substitution);
+        self
+    }
+
+    // A token of a text compared with itself never stands for itself, or every alignment
+    // could fall back on the trivial one of each token with itself
+    pub fn for_same_text(mut self) -> Self {
+        self.same_text = true;
+        self
+    }
+
+    pub fn has_phonetic(&self) -> bool {
+        !self.phonetic.is_empty()
+    }
+
+    // The same costs as if no two tokens sounded alike
+    pub fn without_phonetic(&self) -> Self {
+        EditCosts {
+            phonetic: FxHashMap::default(),
+            ..self.clone()
+        }
+    }
+
+    // The tokens with every group of homophones that cost nothing to substitute under the
+    // lowest key of the group, for the algorithms that only find tokens with equal keys.
+    // None if no homophones are free.
+    pub fn merge_homophones(&self, tokens: &[Token]) -> Option<Vec<Token>> {
+        // Links only ever run from a higher key to a lower one, so every group ends at its lowest
+        let mut lower: FxHashMap<u32, u32> = FxHashMap::default();
+        let root = |lower: &FxHashMap<u32, u32>, mut key: u32| {
+            while let Some(&next) = lower.get(&key) {
+                key = next;
+            }
+            key
+        };
+        for &(x, y) in self.phonetic.iter().filter(|(_, cost)| **cost == 0.0).map(|(pair, _)| pair) {
+            let (x, y) = (root(&lower, x), root(&lower, y));
+            if x != y {
+                lower.insert(x.max(y), x.min(y));
+            }
+        }
+        if lower.is_empty() {
+            return None;
+        }
+        Some(tokens.iter().map(|&token| Token { key: root(&lower, token.key), ..token }).collect())
+    }
+
+    pub fn substitution(&self, a: &Token, b: &Token) -> f32 {
+        if self.same_text && a.start == b.start {
+            f32::INFINITY
+        } else if a == b {
+            0.0
+        } else if self.substitutions.is_empty() && self.phonetic.is_empty() {
+            self.default_substitution
+        } else {
+            let key = ordered(a.key, b.key);
+            let cost = self.substitutions.get(&key).copied().unwrap_or(self.default_substitution);
+            self.phonetic.get(&key).map_or(cost, |&phonetic| cost.min(phonetic))
+        }
+    }
+}
+
+fn insert_lowest(
+    costs: &mut FxHashMap<(u32, u32), f32>,
+    substitutions: impl IntoIterator<Item = (u32, u32, f32)>,
+    default: f32,
+) {
+    for (x, y, cost) in substitutions {
+        if x == y {
+            continue;
+        }
+        let entry = costs.entry(ordered(x, y)).or_insert(default);
+        *entry = entry.min(cost);
+    }
+}
+
+impl Default for EditCosts {
+    fn default() -> Self {
+        EditCosts::new(&CostModel::default(), [])
+    }
+}
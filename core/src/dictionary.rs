@@ -0,0 +1,247 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    normalize::Normalization,
+    synonyms::{Synonym, Token, Word},
+    variants::VariantTable,
+};
+
+// Word forms that count as the same word. With a cost they count as near-synonyms instead,
+// and substituting one form for another costs that much in the edit distances.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DictionaryGroup {
+    pub forms: Vec<String>,
+    pub cost: f32,
+}
+
+// Reads a glossary with one group of equivalent forms per line, separated by tabs. A column
+// like `=0.5` gives the group a cost. Lines starting with '#' are comments.
+pub fn parse_dictionary(table: &str) -> Vec<DictionaryGroup> {
+    table
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(|line| {
+            let mut group = DictionaryGroup::default();
+            for column in line.split('\t').map(str::trim).filter(|column| !column.is_empty()) {
+                match column.strip_prefix('=').and_then(|cost| cost.parse().ok()) {
+                    Some(cost) => group.cost = cost,
+                    None => group.forms.push(column.to_string()),
+                }
+            }
+            group
+        })
+        .filter(|group| group.forms.len() > 1)
+        .collect()
+}
+
+// Word forms that match each other wherever they appear in either text. The forms go through
+// the same normalization and variant mapping as the texts they are looked up in. Every form
+// gets a node for `resolve_synonyms`: the forms of a group share one, unless the group has a
+// cost, in which case each form has its own and they are linked at that cost. A form listed
+// in several groups keeps a single node, which binds the groups together.
+pub struct Dictionary<'a> {
+    nodes: FxHashMap<Vec<char>, usize>,
+    links: Vec<(usize, usize, f32)>,
+    longest: usize,
+    variants: &'a VariantTable,
+}
+
+impl<'a> Dictionary<'a> {
+    pub fn new(groups: &[DictionaryGroup], normalization: &Normalization, variants: &'a VariantTable) -> Self {
+        let mut nodes: FxHashMap<Vec<char>, usize> = FxHashMap::default();
+        let mut links: Vec<(usize, usize, f32)> = Vec::new();
+        let mut next = 0;
+        for group in groups {
+            let forms: Vec<Vec<char>> = group
+                .forms
+                .iter()
+                .map(|word| {
+                    let (normalized, _) = normalization.apply(word);
+                    normalized.chars().map(|c| variants.canonical(c)).collect()
+                })
+                .filter(|form: &Vec<char>| !form.is_empty())
+                .collect();
+            if group.cost > 0.0 {
+                // Each form has a node of its own, the one it already has from an earlier group if any
+                let mut members: Vec<usize> = Vec::new();
+                for form in forms {
+                    let node = *nodes.entry(form).or_insert_with(|| {
+                        next += 1;
+                        next - 1
+                    });
+                    if !members.contains(&node) {
+                        links.extend(members.iter().map(|&other| (other, node, group.cost)));
+                        members.push(node);
+                    }
+                }
+            } else {
+                // The forms share one node, so groups that have a form in common are merged
+                let merged: Vec<usize> = forms.iter().filter_map(|form| nodes.get(form).copied()).collect();
+                let node = merged.iter().copied().min().unwrap_or_else(|| {
+                    next += 1;
+                    next - 1
+                });
+                let ends = links.iter_mut().flat_map(|(x, y, _)| [x, y]);
+                for value in nodes.values_mut().chain(ends).filter(|value| merged.contains(value)) {
+                    *value = node;
+                }
+                for form in forms {
+                    nodes.insert(form, node);
+                }
+            }
+        }
+        Dictionary {
+            longest: nodes.keys().map(Vec::len).max().unwrap_or(0),
+            nodes,
+            links,
+            variants,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // The links between the forms of the groups that have a cost
+    pub fn links(&self) -> &[(usize, usize, f32)] {
+        &self.links
+    }
+
+    // The occurrences of the forms in the text with the node of their form, longest first
+    // from left to right. They have to start and end where the plain tokens do, so a form
+    // never cuts a word or a cluster in two. Positional synonyms take precedence, but one
+    // covering exactly an occurrence joins its group.
+    pub fn find(&self, text: &[char], tokens: &[Token], synonyms: &[Synonym]) -> Vec<(Word, usize)> {
+        let text: Vec<char> = text.iter().map(|&c| self.variants.canonical(c)).collect();
+        let starts: FxHashSet<usize> = tokens.iter().map(|t| t.start).collect();
+        let ends: FxHashSet<usize> = tokens.iter().map(|t| t.end).collect();
+        let words: FxHashSet<(usize, usize)> = synonyms.iter().map(|s| (s.word.start, s.word.end)).collect();
+        let mut covered = vec![false; text.len()];
+        for synonym in synonyms {
+            covered[synonym.word.start..synonym.word.end].fill(true);
+        }
+
+        let mut occurrences = Vec::new();
+        let mut i = 0;
+        while i < text.len() {
+            let found = starts.contains(&i).then(|| {
+                (i + 1..=(i + self.longest).min(text.len())).rev().find_map(|end| {
+                    let free = words.contains(&(i, end)) || !covered[i..end].contains(&true);
+                    let node = self.nodes.get(&text[i..end]).filter(|_| free && ends.contains(&end))?;
+                    Some((Word::new(i, end), *node))
+                })
+            });
+            match found.flatten() {
+                Some((word, node)) => {
+                    occurrences.push((word, node));
+                    i = word.end;
+                }
+                None => i += 1,
+            }
+        }
+        occurrences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        synonyms::{LinkedWords, Vocabulary},
+        test_utils::char_tokens,
+        tokenizer::{self, WordTokenizer},
+    };
+
+    fn group(forms: &[&str], cost: f32) -> DictionaryGroup {
+        DictionaryGroup {
+            forms: forms.iter().map(|form| form.to_string()).collect(),
+            cost,
+        }
+    }
+
+    // The node every char of the text is found with
+    fn nodes(dictionary: &Dictionary, text: &str) -> Vec<usize> {
+        let chars: Vec<char> = text.chars().collect();
+        let occurrences = dictionary.find(&chars, &char_tokens(text), &[]);
+        assert_eq!(occurrences.len(), chars.len());
+        occurrences.into_iter().map(|(_, node)| node).collect()
+    }
+
+    #[test]
+    fn groups_sharing_a_form_are_merged() {
+        let variants = VariantTable::default();
+        let groups = [group(&["曰", "云"], 0.0), group(&["云", "言"], 0.0), group(&["謂", "言"], 0.5)];
+        let dictionary = Dictionary::new(&groups, &Normalization::default(), &variants);
+        let nodes = nodes(&dictionary, "曰云言謂");
+        assert_eq!(nodes[..3], [nodes[0]; 3]);
+        assert_ne!(nodes[3], nodes[0]);
+        assert_eq!(dictionary.links(), [(nodes[3], nodes[0], 0.5)]);
+    }
+
+    #[test]
+    fn merged_groups_keep_their_links() {
+        let variants = VariantTable::default();
+        let groups = [
+            group(&["甲", "乙"], 0.0),
+            group(&["丙", "丁"], 0.0),
+            group(&["丁", "戊"], 0.5),
+            // Only now are the first two groups found to be one
+            group(&["乙", "丙"], 0.0),
+        ];
+        let dictionary = Dictionary::new(&groups, &Normalization::default(), &variants);
+        let nodes = nodes(&dictionary, "甲乙丙丁戊");
+        assert_eq!(nodes[..4], [nodes[0]; 4]);
+        assert_eq!(dictionary.links(), [(nodes[0], nodes[4], 0.5)]);
+    }
+
+    fn spans(occurrences: &[(Word, usize)]) -> Vec<(usize, usize)> {
+        occurrences.iter().map(|(word, _)| (word.start, word.end)).collect()
+    }
+
+    #[test]
+    fn finds_the_longest_forms_on_token_boundaries() {
+        let variants = VariantTable::default();
+        let groups = [group(&["子曰", "子云"], 0.0), group(&["曰", "云"], 0.0), group(&["he", "cat"], 0.0)];
+        let dictionary = Dictionary::new(&groups, &Normalization::default(), &variants);
+        let text: Vec<char> = "子曰云".chars().collect();
+        let occurrences = dictionary.find(&text, &char_tokens("子曰云"), &[]);
+        assert_eq!(spans(&occurrences), [(0, 2), (2, 3)]);
+        assert_ne!(occurrences[0].1, occurrences[1].1);
+
+        // A form inside a word is no occurrence
+        let text: Vec<char> = "the cat".chars().collect();
+        let mut vocabulary = Vocabulary::default();
+        let tokens = tokenizer::tokenize(&WordTokenizer, &text, &[], &LinkedWords::default(), &mut vocabulary);
+        assert_eq!(spans(&dictionary.find(&text, &tokens, &[])), [(4, 7)]);
+    }
+
+    #[test]
+    fn positional_synonyms_take_precedence() {
+        let variants = VariantTable::default();
+        let dictionary = Dictionary::new(&[group(&["子曰", "子云"], 0.0)], &Normalization::default(), &variants);
+        let text: Vec<char> = "子曰子曰".chars().collect();
+        let tokens = char_tokens("子曰子曰");
+        let synonyms = [
+            Synonym::new(Word::new(1, 2), Vec::new()),
+            Synonym::new(Word::new(2, 4), Vec::new()),
+        ];
+        // The first form is cut by a synonym, the second one is exactly a synonym's word
+        assert_eq!(spans(&dictionary.find(&text, &tokens, &synonyms)), [(2, 4)]);
+    }
+
+    #[test]
+    fn forms_are_normalized_like_the_texts() {
+        let variants = VariantTable::new(&["説說".to_string()]);
+        let normalization = Normalization {
+            width_fold: true,
+            ..Normalization::default()
+        };
+        let dictionary = Dictionary::new(&[group(&["說", "ＡＢ"], 0.0)], &normalization, &variants);
+        let text: Vec<char> = "説AB".chars().collect();
+        let occurrences = dictionary.find(&text, &char_tokens("説AB"), &[]);
+        assert_eq!(spans(&occurrences), [(0, 1), (1, 3)]);
+        assert_eq!(occurrences[0].1, occurrences[1].1);
+    }
+}
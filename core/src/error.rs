@@ -0,0 +1,97 @@
+use serde::Serialize;
+use std::{borrow::Cow, fmt};
+
+// Serialized with its fields, so that callers across the wasm boundary can tell the errors apart
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Error {
+    InvalidParameter {
+        name: &'static str,
+        reason: String,
+    },
+    MalformedSynonym {
+        reason: String,
+    },
+    // Texts are named A and B, or by their index in a corpus
+    TextTooShort {
+        text: Cow<'static, str>,
+        length: usize,
+        required: usize,
+    },
+    LimitExceeded {
+        limit: &'static str,
+        value: u64,
+        maximum: u64,
+    },
+    Cancelled,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidParameter { name, reason } => write!(f, "Invalid value for {}: {}", name, reason),
+            Error::MalformedSynonym { reason } => write!(f, "Malformed synonym: {}", reason),
+            Error::TextTooShort {
+                text,
+                length,
+                required,
+            } => write!(
+                f,
+                "Text {} is too short: it has {} tokens but at least {} are needed",
+                text, length, required
+            ),
+            Error::LimitExceeded {
+                limit,
+                value,
+                maximum,
+            } => write!(f, "Limit exceeded for {}: {} is more than the maximum of {}", limit, value, maximum),
+            Error::Cancelled => write!(f, "The analysis was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Conditions that still produce a result, but one the caller should know is incomplete
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Warning {
+    // The search stopped once `limit` matches were found. `dropped` counts the candidates it
+    // left unexamined: the runs of shared n-grams that would each have grown into a match, the
+    // tiles left in the pass, or the pairs of maximal exact matches left in the group. Later
+    // passes and groups of the search are not counted.
+    MaxSubstringsReached { limit: usize, dropped: usize },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::MaxSubstringsReached { limit, dropped } => write!(
+                f,
+                "Stopped after {} matches, {} more candidates were not examined",
+                limit, dropped
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_serialize_with_their_kind_and_fields() {
+        let too_short = Error::TextTooShort {
+            text: "A".into(),
+            length: 2,
+            required: 4,
+        };
+        let json = serde_json::to_value(&too_short).unwrap();
+        assert_eq!(json, serde_json::json!({"kind": "textTooShort", "text": "A", "length": 2, "required": 4}));
+        let malformed = Error::MalformedSynonym {
+            reason: "words 0..2 and 1..3 overlap".to_string(),
+        };
+        assert_eq!(serde_json::to_value(&malformed).unwrap()["kind"], "malformedSynonym");
+        assert_eq!(serde_json::to_value(Error::Cancelled).unwrap(), serde_json::json!({"kind": "cancelled"}));
+    }
+}
@@ -0,0 +1,283 @@
+/*
+* Running-Karp-Rabin Greedy String Tiling, as described by Wise in
+* "String similarity via greedy string tiling and running Karp-Rabin matching".
+* Tiles are exact, maximal and never overlap in either text.
+*/
+use rustc_hash::{FxHashMap, FxHasher};
+use std::{
+    cmp::{max, Reverse},
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    SelfComparison,
+    config::AnalysisConfig,
+    error::Error,
+    progress::Monitor,
+    synonyms::Token,
+    utils::{self, Matches, SubstringResult},
+};
+
+const INITIAL_SEARCH_LENGTH: usize = 20;
+const HASH_BASE: u64 = 0x100000001b3;
+
+#[derive(Clone, Copy)]
+struct Tile {
+    start_a: usize,
+    start_b: usize,
+    len: usize,
+}
+
+// Prefix hashes so that the hash of any window can be found in constant time
+struct RollingHash {
+    prefix: Vec<u64>,
+    powers: Vec<u64>,
+}
+
+impl RollingHash {
+    fn new(tokens: &[Token]) -> Self {
+        let mut prefix = Vec::with_capacity(tokens.len() + 1);
+        let mut powers = Vec::with_capacity(tokens.len() + 1);
+        prefix.push(0u64);
+        powers.push(1u64);
+        for token in tokens {
+            let mut hasher = FxHasher::default();
+            token.hash(&mut hasher);
+            prefix.push(prefix.last().unwrap().wrapping_mul(HASH_BASE).wrapping_add(hasher.finish()));
+            powers.push(powers.last().unwrap().wrapping_mul(HASH_BASE));
+        }
+        RollingHash { prefix, powers }
+    }
+
+    fn window(&self, start: usize, len: usize) -> u64 {
+        self.prefix[start + len].wrapping_sub(self.prefix[start].wrapping_mul(self.powers[len]))
+    }
+}
+
+// For every position, the index of the first marked token at or after it
+fn next_marked(marked: &[bool]) -> Vec<usize> {
+    let mut next = vec![marked.len(); marked.len() + 1];
+    for i in (0..marked.len()).rev() {
+        next[i] = if marked[i] { i } else { next[i + 1] };
+    }
+    next
+}
+
+#[allow(clippy::too_many_arguments)]
+fn scan_patterns(
+    a: &[Token],
+    b: &[Token],
+    hash_a: &RollingHash,
+    hash_b: &RollingHash,
+    marked_a: &[bool],
+    marked_b: &[bool],
+    search_length: usize,
+    self_comparison: SelfComparison,
+) -> Vec<Tile> {
+    let mut matches = Vec::new();
+    if a.len() < search_length || b.len() < search_length {
+        return matches;
+    }
+    let next_a = next_marked(marked_a);
+    let next_b = next_marked(marked_b);
+
+    let mut windows_b: FxHashMap<u64, Vec<usize>> = FxHashMap::default();
+    for (j, &next) in next_b[..=(b.len() - search_length)].iter().enumerate() {
+        if next >= j + search_length {
+            windows_b.entry(hash_b.window(j, search_length)).or_default().push(j);
+        }
+    }
+
+    for i in 0..=(a.len() - search_length) {
+        if next_a[i] < i + search_length {
+            continue;
+        }
+        let Some(candidates) = windows_b.get(&hash_a.window(i, search_length)) else {
+            continue;
+        };
+        for &j in candidates {
+            let mut len = 0;
+            while i + len < a.len()
+                && j + len < b.len()
+                && !marked_a[i + len]
+                && !marked_b[j + len]
+                && a[i + len] == b[j + len]
+                && self_comparison.allows(i, i + len + 1, j)
+            {
+                len += 1;
+            }
+            // Hash collisions are filtered out here
+            if len >= search_length {
+                matches.push(Tile {
+                    start_a: i,
+                    start_b: j,
+                    len,
+                });
+            }
+        }
+    }
+    matches
+}
+
+// Tiles the matches that nothing tiled yet, longest first, and returns how many tiles
+// were left over once there were max_substrings of them
+fn mark_tiles(
+    matches: &mut [Tile],
+    marked_a: &mut [bool],
+    marked_b: &mut [bool],
+    tiles: &mut Vec<Tile>,
+    max_substrings: usize,
+) -> usize {
+    matches.sort_by_key(|m| Reverse(m.len));
+    let mut dropped = 0;
+    for m in matches.iter() {
+        let occluded = marked_a[m.start_a..m.start_a + m.len].iter().any(|&x| x)
+            || marked_b[m.start_b..m.start_b + m.len].iter().any(|&x| x);
+        if occluded {
+            continue;
+        }
+        // A tile left over is still marked, so that the other windows of its match are not counted again
+        marked_a[m.start_a..m.start_a + m.len].fill(true);
+        marked_b[m.start_b..m.start_b + m.len].fill(true);
+        if tiles.len() >= max_substrings {
+            dropped += 1;
+        } else {
+            tiles.push(*m);
+        }
+    }
+    dropped
+}
+
+pub fn find_tiles(a: &[Token], b: &[Token], config: &AnalysisConfig, monitor: &mut Monitor) -> Result<Matches, Error> {
+    let min_len = max(config.min_length, 1);
+    let max_substrings = config.max_substrings;
+    let hash_a = RollingHash::new(a);
+    let hash_b = RollingHash::new(b);
+    let mut marked_a = vec![false; a.len()];
+    let mut marked_b = vec![false; b.len()];
+    let mut tiles: Vec<Tile> = Vec::new();
+    let mut search_length = max(INITIAL_SEARCH_LENGTH, min_len);
+    // The search length halves every pass, so progress is measured on a log scale
+    let passes = (search_length as f32 / min_len as f32).log2().max(1.0);
+
+    // Once the tiles reach max_substrings the passes left only count what they would have tiled,
+    // and the first one to find anything ends the search
+    let dropped = loop {
+        let fraction = 1.0 - (search_length as f32 / min_len as f32).log2() / passes;
+        monitor.report(fraction.clamp(0.0, 1.0), tiles.len())?;
+        let mut matches = scan_patterns(
+            a,
+            b,
+            &hash_a,
+            &hash_b,
+            &marked_a,
+            &marked_b,
+            search_length,
+            config.self_comparison,
+        );
+        let longest = matches.iter().map(|m| m.len).max().unwrap_or(0);
+        if longest > 2 * search_length {
+            // There are much longer matches, so rescan with a bigger window first
+            search_length = longest;
+            continue;
+        }
+        let dropped = mark_tiles(&mut matches, &mut marked_a, &mut marked_b, &mut tiles, max_substrings);
+        if dropped > 0 {
+            break dropped;
+        }
+        if search_length > 2 * min_len {
+            search_length /= 2;
+        } else if search_length > min_len {
+            search_length = min_len;
+        } else {
+            break 0;
+        }
+    };
+
+    let mut ret: Vec<SubstringResult> = tiles
+        .iter()
+        .filter_map(|t| {
+            let len = utils::find_length_from_tokens(
+                &a[t.start_a..t.start_a + t.len],
+                &b[t.start_b..t.start_b + t.len],
+            );
+            if len < min_len {
+                return None;
+            }
+            Some(SubstringResult {
+                start_a: t.start_a,
+                end_a: t.start_a + t.len,
+                start_b: t.start_b,
+                end_b: t.start_b + t.len,
+                len,
+                // Tiles are exact matches
+                edit_ratio: 1.0,
+            })
+        })
+        .collect();
+    ret.sort_unstable_by_key(|x| x.start_a);
+    Ok(Matches { results: ret, dropped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Random, tokens};
+
+    #[test]
+    fn tiles_are_disjoint_and_maximal() {
+        let mut random = Random::new(0x9e3779b97f4a7c15);
+        let config = AnalysisConfig {
+            min_length: 3,
+            ..AnalysisConfig::default()
+        };
+        for _ in 0..200 {
+            let a = tokens(&random.keys(60, 3));
+            let b = tokens(&random.keys(60, 3));
+            let tiles = find_tiles(&a, &b, &config, &mut Monitor::default()).unwrap().results;
+            let mut marked_a = vec![false; a.len()];
+            let mut marked_b = vec![false; b.len()];
+            for tile in &tiles {
+                assert!(tile.end_a - tile.start_a >= config.min_length);
+                assert_eq!(a[tile.start_a..tile.end_a], b[tile.start_b..tile.end_b]);
+                for marked in marked_a[tile.start_a..tile.end_a].iter_mut().chain(&mut marked_b[tile.start_b..tile.end_b]) {
+                    assert!(!*marked, "tiles overlap");
+                    *marked = true;
+                }
+            }
+            // A tile could only have grown over a pair of equal tokens that no tile covers
+            for tile in &tiles {
+                let (start_a, end_a, start_b, end_b) = (tile.start_a, tile.end_a, tile.start_b, tile.end_b);
+                let free = |i: usize, j: usize| !marked_a[i] && !marked_b[j] && a[i] == b[j];
+                assert!(start_a == 0 || start_b == 0 || !free(start_a - 1, start_b - 1));
+                assert!(end_a == a.len() || end_b == b.len() || !free(end_a, end_b));
+            }
+        }
+    }
+
+    #[test]
+    fn reaching_the_limit_between_passes_counts_the_later_tiles() {
+        // The one window of a 20 token match fills the limit with the last candidate of the first
+        // pass, and a 4 token match is left for a later pass
+        let keys: Vec<u32> = (0..20).chain(100..102).chain(30..34).collect();
+        let a = tokens(&keys);
+        let b = tokens(&keys.iter().map(|&key| if key >= 100 { key + 100 } else { key }).collect::<Vec<_>>());
+        let config = AnalysisConfig {
+            min_length: 3,
+            max_substrings: 1,
+            ..AnalysisConfig::default()
+        };
+        let matches = find_tiles(&a, &b, &config, &mut Monitor::default()).unwrap();
+        assert_eq!(matches.results.len(), 1);
+        assert_eq!(matches.results[0].len, 20);
+        // The 4 token match is found at its start and one token in, but it is one tile
+        assert_eq!(matches.dropped, 1);
+        let config = AnalysisConfig {
+            max_substrings: 2,
+            ..config
+        };
+        let matches = find_tiles(&a, &b, &config, &mut Monitor::default()).unwrap();
+        assert_eq!(matches.results.len(), 2);
+        assert_eq!(matches.dropped, 0);
+    }
+}
@@ -0,0 +1,317 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+
+pub use analyzer::Analyzer;
+pub use config::AnalysisConfig;
+pub use corpus::Corpus;
+pub use error::{Error, Warning};
+pub use normalize::clean_text;
+pub use progress::{CancellationToken, Monitor, Progress};
+
+pub mod analyzer;
+pub mod comparativus;
+pub mod config;
+pub mod corpus;
+pub mod costs;
+pub mod dictionary;
+pub mod error;
+pub mod greedy_string_tiling;
+pub mod normalize;
+pub mod phonetic;
+pub mod progress;
+pub mod smith_waterman;
+pub mod suffix_array;
+pub mod synonyms;
+#[cfg(test)]
+mod test_utils;
+pub mod tokenizer;
+pub mod utils;
+pub mod variants;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Algorithm {
+    Comparativus,
+    SmithWaterman,
+    GreedyStringTiling,
+    SuffixArray,
+}
+
+// What a token is: single characters suit Chinese, words suit space separated languages,
+// and grapheme clusters keep marks, variation selectors and emoji sequences in one piece
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Tokenization {
+    Character,
+    Grapheme,
+    Word,
+}
+
+// Comparing a text with itself, to find the passages it repeats. Every passage matches
+// itself at the same offsets, and every repetition would be found from both of its copies,
+// so matches only run from a passage in A to a later one in B.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum SelfComparison {
+    Off,
+    // The later passage may start inside the earlier one, as in a refrain repeated back to back
+    Overlapping,
+    // The earlier passage ends before the later one starts
+    Disjoint,
+}
+
+impl SelfComparison {
+    // How many tokens of A a match starting at these offsets may cover, None if it may not start there
+    pub fn max_len(self, start_a: usize, start_b: usize) -> Option<usize> {
+        match self {
+            SelfComparison::Off => Some(usize::MAX),
+            SelfComparison::Overlapping => (start_a < start_b).then_some(usize::MAX),
+            SelfComparison::Disjoint => (start_a < start_b).then(|| start_b - start_a),
+        }
+    }
+
+    pub fn allows(self, start_a: usize, end_a: usize, start_b: usize) -> bool {
+        self.max_len(start_a, start_b).is_some_and(|max_len| end_a - start_a <= max_len)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ResponseAndOverall {
+    pub result: Vec<utils::Result>,
+    // For a text compared with itself, the share of it that its repeats cover
+    pub overall_levenstein_similarity: f32,
+    pub overall_cosine_similarity: f32,
+    pub truncated: bool,
+    pub warnings: Vec<Warning>,
+    pub synonym_classes: Vec<synonyms::SynonymClass>,
+}
+
+pub fn analyze(
+    str_a: &str,
+    str_b: &str,
+    synonyms_a: Vec<synonyms::Synonym>,
+    synonyms_b: Vec<synonyms::Synonym>,
+    config: &AnalysisConfig,
+) -> Result<ResponseAndOverall, Error> {
+    analyze_with_monitor(str_a, str_b, synonyms_a, synonyms_b, config, &mut Monitor::default())
+}
+
+// Reports progress to the monitor and stops with Error::Cancelled once its token is cancelled
+pub fn analyze_with_monitor(
+    str_a: &str,
+    str_b: &str,
+    synonyms_a: Vec<synonyms::Synonym>,
+    synonyms_b: Vec<synonyms::Synonym>,
+    config: &AnalysisConfig,
+    monitor: &mut Monitor,
+) -> Result<ResponseAndOverall, Error> {
+    let mut analyzer = Analyzer::new(str_a, str_b);
+    analyzer.set_synonyms(synonyms_a, synonyms_b);
+    analyzer.set_config(config.clone());
+    analyzer.run(monitor)
+}
+
+// The edit ratio of the whole texts. Long texts make billions of cells, so progress is
+// reported and cancelling checked on every row.
+fn overall_similarity(
+    token_a: &[synonyms::Token],
+    token_b: &[synonyms::Token],
+    costs: &costs::EditCosts,
+    matches: &[utils::SubstringResult],
+    config: &AnalysisConfig,
+    monitor: &mut Monitor,
+) -> Result<f32, Error> {
+    if config.self_comparison != SelfComparison::Off {
+        monitor.report(1.0, 0)?;
+        return Ok(repeated_share(token_a, matches));
+    }
+    let mut distance = utils::EditDistance::new(costs);
+    for token in token_b {
+        distance.push_b(token, std::iter::empty());
+    }
+    for (i, token) in token_a.iter().enumerate() {
+        monitor.report(i as f32 / token_a.len() as f32, 0)?;
+        distance.push_a(token, token_b.iter());
+    }
+    monitor.report(1.0, 0)?;
+    let len = utils::find_length_from_tokens(token_a, token_b);
+    Ok(utils::edit_ratio(len, distance.distance()))
+}
+
+// The share of a text compared with itself that its repeats cover, on either side of a match.
+// The text is always equal to itself, so its edit ratio would say nothing.
+fn repeated_share(tokens: &[synonyms::Token], matches: &[utils::SubstringResult]) -> f32 {
+    let mut repeated = vec![false; tokens.len()];
+    for ma in matches {
+        repeated[ma.start_a..ma.end_a].fill(true);
+        repeated[ma.start_b..ma.end_b].fill(true);
+    }
+    let len: usize = tokens.iter().map(|token| token.len()).sum();
+    if len == 0 {
+        return 0.0;
+    }
+    let covered: usize = tokens.iter().zip(repeated).filter(|x| x.1).map(|x| x.0.len()).sum();
+    covered as f32 / len as f32
+}
+
+// Turns the matches into the response, filling the gaps between them with cosine similarities.
+// The monitor follows the overall similarity.
+#[allow(clippy::too_many_arguments)]
+fn build_response(
+    file_a: &[char],
+    file_b: &[char],
+    token_a: &[synonyms::Token],
+    token_b: &[synonyms::Token],
+    costs: &costs::EditCosts,
+    matches: utils::Matches,
+    config: &AnalysisConfig,
+    monitor: &mut Monitor,
+) -> Result<ResponseAndOverall, Error> {
+    let mut warnings = Vec::new();
+    if matches.dropped > 0 {
+        warnings.push(Warning::MaxSubstringsReached {
+            limit: config.max_substrings,
+            dropped: matches.dropped,
+        });
+    }
+    let truncated = !warnings.is_empty();
+    let overall_levenstein_similarity = overall_similarity(token_a, token_b, costs, &matches.results, config, monitor)?;
+    let levenshtein_distances = matches.results;
+    if levenshtein_distances.is_empty() {
+        return Ok(ResponseAndOverall {
+            overall_levenstein_similarity,
+            overall_cosine_similarity: utils::cosine_similarity(file_a, file_b),
+            result: vec![],
+            truncated,
+            warnings,
+            synonym_classes: Vec::new(),
+        });
+    }
+
+    let mut matches_a = levenshtein_distances[..].to_vec();
+    matches_a.sort_unstable_by_key(|x| x.start_a);
+    let mut matches_b = levenshtein_distances[..].to_vec();
+    matches_b.sort_unstable_by_key(|x| x.start_b);
+
+    let mut result: Vec<utils::Result> = Vec::with_capacity(levenshtein_distances.len() * 2 + 1);
+
+    // Matches that score lower without the homophones are phonetic ones
+    let plain = costs.has_phonetic().then(|| costs.without_phonetic());
+    let mut add_levenshtein_match = |elem: &utils::SubstringResult| {
+        let a = utils::Substring {
+            start: token_a[elem.start_a].start,
+            end: token_a[elem.end_a-1].end,
+        };
+        let b = utils::Substring {
+            start: token_b[elem.start_b].start,
+            end: token_b[elem.end_b-1].end,
+        };
+        let similarity = elem.edit_ratio;
+        let phonetic = plain.as_ref().is_some_and(|plain| {
+            utils::recompute_ratio(token_a, token_b, plain, elem.start_a, elem.end_a, elem.start_b, elem.end_b, elem.len)
+                < similarity
+        });
+        result.push(utils::Result {
+            a,
+            b,
+            similarity,
+            levenshtein_match: true,
+            phonetic,
+        });
+    };
+
+    matches_a.iter().for_each(|elem| {
+        add_levenshtein_match(elem);
+    });
+
+    // Merge overlapping matches in both lists
+    matches_a.dedup_by(|a, b| {
+        if a.start_a <= b.end_a && b.start_a <= a.end_a {
+            a.start_a = a.start_a.min(b.start_a);
+            a.end_a = a.end_a.max(b.end_a);
+            b.start_a = a.start_a;
+            b.end_a = a.end_a;
+            true
+        } else {
+            false
+        }
+    });
+    matches_b.dedup_by(|a, b| {
+        if a.start_b <= b.end_b && b.start_b <= a.end_b {
+            a.start_b = a.start_b.min(b.start_b);
+            a.end_b = a.end_b.max(b.end_b);
+            b.start_b = a.start_b;
+            b.end_b = a.end_b;
+            true
+        } else {
+            false
+        }
+    });
+
+    let add_cosine_similarity_to_result = |cosine: &mut utils::Result| {
+        if cosine.a.start < cosine.a.end && cosine.b.start < cosine.b.end {
+            cosine.similarity = utils::cosine_similarity(
+                &file_a[cosine.a.start..cosine.a.end],
+                &file_b[cosine.b.start..cosine.b.end],
+            );
+        } else {
+            cosine.similarity = 0.0;
+        }
+        *cosine
+    };
+    let substract_one_if_not_0 = |x: usize| if x > 0 { x - 1 } else { 0 };
+    if matches_a.first().unwrap().start_a > 1 && matches_b.first().unwrap().start_b > 1 {
+        result.push(add_cosine_similarity_to_result(&mut utils::Result {
+            a: utils::Substring {
+                start: 0,
+                end: token_a[matches_a.first().unwrap().start_a-1].end,
+            },
+            b: utils::Substring {
+                start: 0,
+                end: token_b[matches_b.first().unwrap().start_b-1].end,
+            },
+            similarity: 0.0,
+            levenshtein_match: false,
+            phonetic: false,
+        }));
+    }
+    for (tokens_a, tokens_b) in matches_a.windows(2).zip(matches_b.windows(2)) {
+        // Get the area between two matches in from tokens_a and b
+        let mut cosine = utils::Result {
+            a: utils::Substring {
+                start: token_a[min(tokens_a[0].end_a, token_a.len() - 1)].start,
+                end: token_a[substract_one_if_not_0(tokens_a[1].start_a)].end,
+            },
+            b: utils::Substring {
+                start: token_b[min(tokens_b[0].end_b, token_b.len() - 1)].start,
+                end: token_b[substract_one_if_not_0(tokens_b[1].start_b)].end,
+            },
+            similarity: 0.0,
+            levenshtein_match: false,
+            phonetic: false,
+        };
+        result.push(add_cosine_similarity_to_result(&mut cosine));
+    }
+    if matches_a.last().unwrap().end_a < token_a.len() - 1
+        && matches_b.last().unwrap().end_b < token_b.len() - 1
+    {
+        result.push(add_cosine_similarity_to_result(&mut utils::Result {
+            a: utils::Substring {
+                start: token_a[matches_a.last().unwrap().end_a].start,
+                end: file_a.len(),
+            },
+            b: utils::Substring {
+                start: token_b[matches_b.last().unwrap().end_b].start,
+                end: file_b.len(),
+            },
+            similarity: 0.0,
+            levenshtein_match: false,
+            phonetic: false,
+        }));
+    }
+    Ok(ResponseAndOverall {
+        overall_levenstein_similarity,
+        overall_cosine_similarity: utils::cosine_similarity(file_a, file_b),
+        result,
+        truncated,
+        warnings,
+        synonym_classes: Vec::new(),
+    })
+}
@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use std::iter;
+use unicode_normalization::{
+    IsNormalized, UnicodeNormalization,
+    char::{canonical_combining_class, decompose_compatible},
+    is_nfc_quick,
+};
+use unicode_properties::{GeneralCategoryGroup, UnicodeGeneralCategory};
+
+use crate::utils::Substring;
+
+// The marks clean_text has always removed, kept as they are so that old projects still line up
+const PUNCTUATION: [char; 43] = [
+    '.', ',', '，', '。', '：', '；', '「', '」', '？', '\n', '、', '·', '》', '《', '“', '”', '‘',
+    '’', '！', '（', '）', '【', '】', '『', '』', '—', '～', '\r', '\t', ' ', '*', '!', '?', ':',
+    ';', '(', ')', '[', ']', '{', '}', '<', '>',
+];
+
+pub fn clean_text(text: &str) -> String {
+    let mut text = text.to_string();
+    text.retain(|c| !PUNCTUATION.contains(&c));
+    text
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CharClass {
+    // The fixed list that clean_text removes
+    Classic,
+    Punctuation,
+    Symbol,
+    Whitespace,
+    Number,
+    Chars(String),
+}
+
+impl CharClass {
+    fn contains(&self, c: char) -> bool {
+        match self {
+            CharClass::Classic => PUNCTUATION.contains(&c),
+            CharClass::Punctuation => c.general_category_group() == GeneralCategoryGroup::Punctuation,
+            CharClass::Symbol => c.general_category_group() == GeneralCategoryGroup::Symbol,
+            CharClass::Whitespace => c.is_whitespace(),
+            CharClass::Number => c.general_category_group() == GeneralCategoryGroup::Number,
+            CharClass::Chars(chars) => chars.contains(c),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum UnicodeForm {
+    Nfc,
+    Nfkc,
+}
+
+// The steps run in the order of the fields, except that dropped chars are never collapsed
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Normalization {
+    pub width_fold: bool,
+    pub unicode_form: Option<UnicodeForm>,
+    pub case_fold: bool,
+    pub drop: Vec<CharClass>,
+    pub collapse_whitespace: bool,
+}
+
+// Full-width ASCII and the half-width katakana and symbols, plus the ideographic space
+fn is_width_variant(c: char) -> bool {
+    ('\u{FF01}'..='\u{FFEE}').contains(&c) || c == '\u{3000}'
+}
+
+impl Normalization {
+    pub fn is_identity(&self) -> bool {
+        *self == Normalization::default()
+    }
+
+    // Whether c may change the char before it, like a combining mark or a half-width voicing mark
+    fn joins_previous(&self, c: char) -> bool {
+        let compatible = self.unicode_form == Some(UnicodeForm::Nfkc) || (self.width_fold && is_width_variant(c));
+        let mut first = None;
+        if compatible {
+            decompose_compatible(c, |x| {
+                first.get_or_insert(x);
+            });
+        }
+        let first = first.unwrap_or(c);
+        canonical_combining_class(first) != 0 || is_nfc_quick(iter::once(first)) != IsNormalized::Yes
+    }
+
+    fn normalize_chunk(&self, chunk: &[char]) -> Vec<char> {
+        let mut chars = chunk.to_vec();
+        if self.width_fold && chars.iter().any(|&c| is_width_variant(c)) {
+            let mut folded = Vec::with_capacity(chars.len());
+            for c in chars {
+                if is_width_variant(c) {
+                    decompose_compatible(c, |x| folded.push(x));
+                } else {
+                    folded.push(c);
+                }
+            }
+            // Half-width voicing marks turn into combining ones that belong on the kana before them
+            chars = folded.into_iter().nfc().collect();
+        }
+        chars = match self.unicode_form {
+            Some(UnicodeForm::Nfc) => chars.into_iter().nfc().collect(),
+            Some(UnicodeForm::Nfkc) => chars.into_iter().nfkc().collect(),
+            None => chars,
+        };
+        if self.case_fold {
+            chars = chars.into_iter().flat_map(char::to_lowercase).collect();
+        }
+        chars
+    }
+
+    // Chars that combine are normalized together, and everything they turn into
+    // maps back to all of them
+    pub fn apply(&self, text: &str) -> (String, OffsetMap) {
+        let original: Vec<char> = text.chars().collect();
+        let needs_chunks = self.width_fold || self.unicode_form.is_some();
+        let mut normalized = String::with_capacity(text.len());
+        let mut starts = Vec::with_capacity(original.len());
+        let mut ends = Vec::with_capacity(original.len());
+        let mut after_whitespace = false;
+        let mut start = 0;
+        while start < original.len() {
+            let mut end = start + 1;
+            while needs_chunks && end < original.len() && self.joins_previous(original[end]) {
+                end += 1;
+            }
+            for c in self.normalize_chunk(&original[start..end]) {
+                if self.drop.iter().any(|class| class.contains(c)) {
+                    continue;
+                }
+                if self.collapse_whitespace && c.is_whitespace() {
+                    if after_whitespace {
+                        *ends.last_mut().unwrap() = end;
+                        continue;
+                    }
+                    after_whitespace = true;
+                    normalized.push(' ');
+                } else {
+                    after_whitespace = false;
+                    normalized.push(c);
+                }
+                starts.push(start);
+                ends.push(end);
+            }
+            start = end;
+        }
+        (
+            normalized,
+            OffsetMap {
+                starts,
+                ends,
+                original_len: original.len(),
+            },
+        )
+    }
+}
+
+// Maps char offsets in a normalized text back to the text it was normalized from
+#[derive(Clone, Debug)]
+pub struct OffsetMap {
+    // The original span that every normalized char came from
+    starts: Vec<usize>,
+    ends: Vec<usize>,
+    original_len: usize,
+}
+
+impl OffsetMap {
+    pub fn original_len(&self) -> usize {
+        self.original_len
+    }
+
+    // The first original char of the span up to the last one, so removed chars
+    // around the span are left out and the ones inside it are kept
+    pub fn to_original(&self, substring: Substring) -> Substring {
+        if substring.start >= substring.end {
+            let start = self.starts.get(substring.start).copied().unwrap_or(self.original_len);
+            return Substring { start, end: start };
+        }
+        Substring {
+            start: self.starts[substring.start],
+            end: self.ends[substring.end - 1],
+        }
+    }
+
+    // The offset in the normalized text of the first char that starts at or after `offset`
+    pub fn to_normalized(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&x| x < offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_round_trip() {
+        let normalization = Normalization {
+            width_fold: true,
+            case_fold: true,
+            drop: vec![CharClass::Punctuation],
+            collapse_whitespace: true,
+            ..Normalization::default()
+        };
+        let text = "Ａｂ，  ｃ\u{3000}ｄ！e";
+        let (normalized, offsets) = normalization.apply(text);
+        assert_eq!(normalized, "ab c de");
+        assert_eq!(offsets.original_len(), text.chars().count());
+        let original = |start, end| {
+            let original = offsets.to_original(Substring { start, end });
+            (original.start, original.end)
+        };
+        for i in 0..normalized.chars().count() {
+            let (start, end) = original(i, i + 1);
+            assert!(start < end);
+            assert_eq!(offsets.to_normalized(start), i);
+        }
+        // The collapsed spaces map back to all of them, the dropped comma before them is left out
+        assert_eq!(original(2, 3), (3, 5));
+        // The dropped mark inside a span stays in it
+        assert_eq!(original(5, 7), (7, 10));
+        assert_eq!(offsets.to_normalized(2), 2);
+    }
+
+    // The levenshtein matches between a and b, as offsets in the texts as given
+    fn matches(a: &str, b: &str, normalization: Normalization) -> Vec<(usize, usize, usize, usize)> {
+        let config = crate::AnalysisConfig {
+            normalization,
+            ..crate::AnalysisConfig::default()
+        };
+        let response = crate::analyze(a, b, Vec::new(), Vec::new(), &config).unwrap();
+        let matches = response.result.iter().filter(|x| x.levenshtein_match);
+        matches.map(|x| (x.a.start, x.a.end, x.b.start, x.b.end)).collect()
+    }
+
+    #[test]
+    fn folded_forms_match_their_counterparts() {
+        let cases = [
+            // Half-width voicing marks fold into the kana before them
+            (
+                "序ＡＢＣｶﾞｷﾞｸﾞｹﾞ跋",
+                "序ABCガギグゲ跋",
+                Normalization {
+                    width_fold: true,
+                    ..Normalization::default()
+                },
+                (0, 13, 0, 9),
+            ),
+            // Ligatures turn into the letters they stand for
+            (
+                "甲ﬁrst ﬂoor oﬃce乙",
+                "甲first floor office乙",
+                Normalization {
+                    unicode_form: Some(UnicodeForm::Nfkc),
+                    ..Normalization::default()
+                },
+                (0, 16, 0, 20),
+            ),
+            // The dotted capital I lowers to an i and a combining dot
+            (
+                "前İSTANBUL IS BIG後",
+                "前i\u{307}stanbul is big後",
+                Normalization {
+                    case_fold: true,
+                    ..Normalization::default()
+                },
+                (0, 17, 0, 18),
+            ),
+        ];
+        for (a, b, normalization, expected) in cases {
+            assert_eq!(matches(a, b, Normalization::default()), [], "{}", a);
+            assert_eq!(matches(a, b, normalization), [expected], "{}", a);
+        }
+    }
+}
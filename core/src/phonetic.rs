@@ -0,0 +1,128 @@
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    costs::{self, SubstitutionGroup},
+    synonyms::Token,
+    variants::VariantTable,
+};
+
+// Characters that share a reading, like the homophones scribes wrote for one another
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Phonetic {
+    // Groups of characters with the same reading, see `parse_readings`
+    pub readings: Vec<String>,
+    // What substituting a homophone costs. At 0 it counts as the same character, for the
+    // algorithms that only match equal tokens too.
+    pub cost: f32,
+}
+
+impl Phonetic {
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+
+    // The substitutions between the single character tokens of the texts that sound alike
+    pub fn char_substitutions(&self, texts: &[(&[char], &[Token])], variants: &VariantTable) -> Vec<(u32, u32, f32)> {
+        let groups: Vec<SubstitutionGroup> = self
+            .readings
+            .iter()
+            .map(|chars| SubstitutionGroup {
+                chars: chars.clone(),
+                cost: self.cost,
+            })
+            .collect();
+        costs::char_substitutions(&groups, texts, variants)
+    }
+}
+
+// Reads a table with the characters and then their readings on every line, separated by
+// whitespace, such as pinyin or a Middle Chinese reconstruction. Characters with several
+// readings are homophones of the characters of each of them. Lines starting with '#' are
+// comments. Returns the groups of characters that share a reading.
+pub fn parse_readings(table: &str) -> Vec<String> {
+    let mut groups: FxHashMap<&str, String> = FxHashMap::default();
+    let mut order = Vec::new();
+    for line in table.lines().filter(|line| !line.trim_start().starts_with('#')) {
+        let mut columns = line.split_whitespace();
+        let Some(chars) = columns.next() else {
+            continue;
+        };
+        for reading in columns {
+            let group = groups.entry(reading).or_insert_with(|| {
+                order.push(reading);
+                String::new()
+            });
+            for c in chars.chars() {
+                if !group.contains(c) {
+                    group.push(c);
+                }
+            }
+        }
+    }
+    order
+        .into_iter()
+        .filter_map(|reading| groups.remove(reading))
+        .filter(|group| group.chars().nth(1).is_some())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Algorithm, config::AnalysisConfig, costs::EditCosts, test_utils::char_tokens};
+
+    #[test]
+    fn characters_with_several_readings_join_each_group() {
+        let table = "# char reading\n說 shuo1 yue4\n悅\tyue4\n稅 shui4\n税 shui4\n";
+        assert_eq!(parse_readings(table), ["說悅", "稅税"]);
+    }
+
+    #[test]
+    fn free_homophones_share_the_lowest_key() {
+        let tokens = char_tokens("說悅稅");
+        let (x, y, z) = (tokens[0].key, tokens[1].key, tokens[2].key);
+        let merged = EditCosts::default().with_phonetic([(y, x, 0.0)]).merge_homophones(&tokens).unwrap();
+        assert_eq!(merged.iter().map(|token| token.key).collect::<Vec<_>>(), [x.min(y), x.min(y), z]);
+        assert!(EditCosts::default().with_phonetic([(x, y, 0.5)]).merge_homophones(&tokens).is_none());
+    }
+
+    #[test]
+    fn every_algorithm_matches_through_homophones() {
+        let a = "學而時習之不亦說乎有朋自遠方來";
+        let b = "學而時習之不亦悅乎有朋自遠方來";
+        let phonetic = Phonetic {
+            readings: vec!["說悅".to_string()],
+            cost: 0.0,
+        };
+        for algorithm in [
+            Algorithm::Comparativus,
+            Algorithm::SmithWaterman,
+            Algorithm::GreedyStringTiling,
+            Algorithm::SuffixArray,
+        ] {
+            let run = |phonetic: Phonetic| {
+                let config = AnalysisConfig {
+                    algorithm,
+                    phonetic,
+                    ..AnalysisConfig::default()
+                };
+                let response = crate::analyze(a, b, Vec::new(), Vec::new(), &config).unwrap();
+                let matches = response.result.into_iter().filter(|x| x.levenshtein_match);
+                matches.map(|x| (x.a.start, x.a.end, x.similarity, x.phonetic)).collect::<Vec<_>>()
+            };
+            assert_eq!(run(phonetic.clone()), [(0, 15, 1.0, true)], "{:?}", algorithm);
+            assert!(run(Phonetic::default()).iter().all(|x| !x.3), "{:?}", algorithm);
+        }
+        // A cheap homophone only counts for the algorithms that allow edits
+        let config = AnalysisConfig {
+            phonetic: Phonetic { cost: 0.5, ..phonetic },
+            ..AnalysisConfig::default()
+        };
+        let response = crate::analyze(a, b, Vec::new(), Vec::new(), &config).unwrap();
+        let matched = &response.result[0];
+        assert_eq!((matched.a.start, matched.a.end, matched.phonetic), (0, 15, true));
+        assert_eq!(matched.similarity, 14.5 / 15.0);
+    }
+}
@@ -0,0 +1,135 @@
+use serde::Serialize;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use crate::error::Error;
+
+// Calling back into JS is slow, so progress is only reported in steps of this size
+const REPORT_STEP: f32 = 0.01;
+
+// Cloned tokens share their state, so one can be handed to another thread to cancel the analysis
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Progress {
+    // Share of the work done so far, between 0 and 1
+    pub fraction: f32,
+    pub matches: usize,
+}
+
+pub struct Monitor<'a> {
+    on_progress: Box<dyn FnMut(Progress) + 'a>,
+    cancellation: CancellationToken,
+    last_reported: f32,
+}
+
+impl<'a> Monitor<'a> {
+    pub fn new(on_progress: impl FnMut(Progress) + 'a, cancellation: CancellationToken) -> Self {
+        Monitor {
+            on_progress: Box::new(on_progress),
+            cancellation,
+            last_reported: f32::NEG_INFINITY,
+        }
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    pub fn check(&self) -> Result<(), Error> {
+        if self.cancellation.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    // The callback may cancel the token itself, so it is checked after reporting
+    pub fn report(&mut self, fraction: f32, matches: usize) -> Result<(), Error> {
+        if fraction >= 1.0 || fraction - self.last_reported >= REPORT_STEP {
+            self.last_reported = fraction;
+            (self.on_progress)(Progress { fraction, matches });
+        }
+        self.check()
+    }
+
+    // A monitor for one of `parts` equal parts of the work, when `done` of them are done
+    // and have found `matches` matches so far. It shares the cancellation token.
+    pub fn part(&mut self, done: usize, parts: usize, matches: usize) -> Monitor<'_> {
+        let cancellation = self.cancellation.clone();
+        Monitor::new(
+            move |progress| {
+                let fraction = (done as f32 + progress.fraction) / parts as f32;
+                // Cancelling shows in the shared token, which the part checks itself
+                let _ = self.report(fraction, matches + progress.matches);
+            },
+            cancellation,
+        )
+    }
+}
+
+impl Default for Monitor<'_> {
+    fn default() -> Self {
+        Monitor::new(|_| {}, CancellationToken::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Algorithm, config::AnalysisConfig, test_utils::Random};
+
+    #[test]
+    fn cancelling_midway_stops_every_algorithm() {
+        let mut random = Random::new(11);
+        let mut text = || (0..2000).map(|_| char::from_u32(0x4e00 + random.below(20) as u32).unwrap()).collect::<String>();
+        let (a, b) = (text(), text());
+        // The search is the first half of the work, the overall similarity the second
+        for cancel_after in [0.0, 0.5] {
+            for algorithm in [
+                Algorithm::Comparativus,
+                Algorithm::SmithWaterman,
+                Algorithm::GreedyStringTiling,
+                Algorithm::SuffixArray,
+            ] {
+                let config = AnalysisConfig {
+                    algorithm,
+                    ..AnalysisConfig::default()
+                };
+                let cancellation = CancellationToken::new();
+                let mut reported = Vec::new();
+                let mut monitor = Monitor::new(
+                    |progress: Progress| {
+                        reported.push(progress.fraction);
+                        if progress.fraction > cancel_after {
+                            cancellation.cancel();
+                        }
+                    },
+                    cancellation.clone(),
+                );
+                let result = crate::analyze_with_monitor(&a, &b, Vec::new(), Vec::new(), &config, &mut monitor);
+                drop(monitor);
+                assert_eq!(result.unwrap_err(), Error::Cancelled, "{:?} {}", algorithm, cancel_after);
+                // Nothing is reported after the progress that cancelled
+                let cancelled_at = reported.iter().position(|&fraction| fraction > cancel_after).unwrap();
+                assert_eq!(cancelled_at, reported.len() - 1, "{:?} {}", algorithm, cancel_after);
+                assert!(reported[cancelled_at] < 1.0, "{:?} {}", algorithm, cancel_after);
+            }
+        }
+    }
+}
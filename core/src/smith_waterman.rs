@@ -0,0 +1,334 @@
+/*
+* Smith-Waterman local alignment over token streams.
+* Every cell remembers where its alignment started, so a single pass over the
+* matrix yields the best alignment for every starting point, which are then
+* picked greedily by score so that they do not overlap.
+*/
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::cmp::max;
+
+use crate::{
+    config::AnalysisConfig,
+    costs::EditCosts,
+    error::Error,
+    progress::Monitor,
+    synonyms::Token,
+    utils::{self, EditDistance, EfficientMatrix, Matches, SubstringResult},
+};
+
+// Beyond this the alignment takes minutes, so callers should use a seeded algorithm instead
+const MAX_CELLS: u64 = 5_000_000_000;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Scoring {
+    pub match_score: i32,
+    pub mismatch_score: i32,
+    pub gap_score: i32,
+}
+
+impl Scoring {
+    pub fn new(match_score: i32, mismatch_score: i32, gap_score: i32) -> Self {
+        Scoring {
+            match_score,
+            mismatch_score,
+            gap_score,
+        }
+    }
+
+    // Partial credit for near-synonyms, in proportion to their substitution cost
+    fn substitution(&self, cost: f32) -> i32 {
+        if cost == 0.0 {
+            self.match_score
+        } else if cost >= 1.0 {
+            self.mismatch_score
+        } else {
+            let score = self.match_score as f32 + (self.mismatch_score - self.match_score) as f32 * cost;
+            score.round() as i32
+        }
+    }
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Scoring {
+            match_score: 2,
+            mismatch_score: -1,
+            gap_score: -1,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Cell {
+    score: i32,
+    start_a: usize,
+    start_b: usize,
+}
+
+struct Alignment {
+    score: i32,
+    start_a: usize,
+    end_a: usize,
+    start_b: usize,
+    end_b: usize,
+}
+
+impl Alignment {
+    fn overlaps(&self, other: &Alignment) -> bool {
+        (self.start_a < other.end_a && other.start_a < self.end_a)
+            || (self.start_b < other.end_b && other.start_b < self.end_b)
+    }
+}
+
+// The lowest score of an alignment of at least min_len at the requested ratio, so that
+// alignments scoring below it need not be remembered. Every token of the alignment scores
+// the match score at most half a point short of rounding, less what its edits take away:
+// a substitution of cost c loses at most c times the mismatch penalty, an insertion or
+// deletion the gap penalty and a transposition two mismatches, each for its cost.
+// Edits that cost nothing give no bound.
+fn min_score(config: &AnalysisConfig, costs: &EditCosts, longest_token: usize) -> i32 {
+    let scoring = config.scoring;
+    let mismatch = (scoring.match_score - scoring.mismatch_score) as f32;
+    let gap = (scoring.match_score - scoring.gap_score) as f32;
+    let mut loss_per_cost = mismatch.max(gap / costs.insertion).max(gap / costs.deletion);
+    if let Some(transposition) = costs.transposition {
+        loss_per_cost = loss_per_cost.max(2.0 * mismatch / transposition);
+    }
+    // The length counts the longest tokens, so an alignment has at least len / longest_token of them
+    let per_char =
+        (scoring.match_score as f32 - 0.5) / longest_token as f32 - loss_per_cost * (1.0 - config.ratio);
+    let score = per_char * config.min_length as f32;
+    if score.is_finite() { max(1, score as i32) } else { 1 }
+}
+
+// The score does not hold an alignment to the ratio: a gap or mismatch between stretches
+// of matches is kept whenever the matches after it score more than it costs. So the
+// alignment is trimmed back to its longest start that keeps the ratio, stepping through
+// both texts together as Comparativus trims its seeds, and ending on tokens that match.
+// None if that is shorter than min_len.
+fn trim_to_ratio(
+    a: &[Token],
+    b: &[Token],
+    costs: &EditCosts,
+    alignment: &Alignment,
+    ratio: f32,
+    min_len: usize,
+) -> Option<SubstringResult> {
+    let (start_a, start_b) = (alignment.start_a, alignment.start_b);
+    let (mut end_a, mut end_b, mut len) = (start_a, start_b, 0);
+    let mut distance = EditDistance::new(costs);
+    let mut trimmed = None;
+    while end_a < alignment.end_a || end_b < alignment.end_b {
+        let step_a = (end_a < alignment.end_a).then(|| &a[end_a]);
+        let step_b = (end_b < alignment.end_b).then(|| &b[end_b]);
+        if let Some(token) = step_a {
+            distance.push_a(token, b[start_b..end_b].iter());
+            end_a += 1;
+        }
+        if let Some(token) = step_b {
+            distance.push_b(token, a[start_a..end_a].iter());
+            end_b += 1;
+        }
+        len += step_a.map_or(0, Token::len).max(step_b.map_or(0, Token::len));
+        // Past a gap the steps pair other tokens than the alignment does, which ends on a match itself
+        let whole = end_a == alignment.end_a && end_b == alignment.end_b;
+        let matched = whole || step_a.zip(step_b).is_some_and(|(x, y)| costs.substitution(x, y) == 0.0);
+        let edit_ratio = utils::edit_ratio(len, distance.distance());
+        if matched && len >= min_len && edit_ratio >= ratio {
+            trimmed = Some(SubstringResult {
+                start_a,
+                end_a,
+                start_b,
+                end_b,
+                len,
+                edit_ratio,
+            });
+        }
+    }
+    trimmed
+}
+
+pub fn find_local_alignments(
+    a: &[Token],
+    b: &[Token],
+    costs: &EditCosts,
+    config: &AnalysisConfig,
+    monitor: &mut Monitor,
+) -> Result<Matches, Error> {
+    let scoring = config.scoring;
+    let min_len = config.min_length;
+    let cells = a.len() as u64 * b.len() as u64;
+    if cells > MAX_CELLS {
+        return Err(Error::LimitExceeded {
+            limit: "alignment matrix cells",
+            value: cells,
+            maximum: MAX_CELLS,
+        });
+    }
+    let longest_token = a.iter().chain(b).map(Token::len).max().unwrap_or(1);
+    let min_score = min_score(config, costs, longest_token);
+    let empty = Cell {
+        score: 0,
+        start_a: 0,
+        start_b: 0,
+    };
+    let mut h = EfficientMatrix::new(empty, b.len() + 1);
+    let mut best: FxHashMap<(usize, usize), Alignment> = FxHashMap::default();
+
+    for j in 0..(b.len() + 1) {
+        h[0][j] = empty;
+    }
+    for i in 1..(a.len() + 1) {
+        // Until the end it is not known which of these alignments will be kept
+        monitor.report((i - 1) as f32 / a.len() as f32, best.len())?;
+        h[i][0] = empty;
+        for j in 1..(b.len() + 1) {
+            // Alignments of a text with itself stay clear of the pairs it does not allow
+            if !config.self_comparison.allows(i - 1, i, j - 1) {
+                h[i][j] = empty;
+                continue;
+            }
+            // An alignment carried on to this cell has to stay clear of them as well
+            let allowed = |cell: &Cell| config.self_comparison.allows(cell.start_a, i, cell.start_b);
+            let diagonal = h[i - 1][j - 1];
+            let substitution = scoring.substitution(costs.substitution(&a[i - 1], &b[j - 1]));
+            let mut cell = if diagonal.score > 0 && allowed(&diagonal) {
+                Cell {
+                    score: diagonal.score + substitution,
+                    ..diagonal
+                }
+            } else {
+                // Start a new alignment at this pair of tokens
+                Cell {
+                    score: substitution,
+                    start_a: i - 1,
+                    start_b: j - 1,
+                }
+            };
+            let up = h[i - 1][j];
+            if up.score + scoring.gap_score > cell.score && allowed(&up) {
+                cell = Cell {
+                    score: up.score + scoring.gap_score,
+                    ..up
+                };
+            }
+            let left = h[i][j - 1];
+            if left.score + scoring.gap_score > cell.score && allowed(&left) {
+                cell = Cell {
+                    score: left.score + scoring.gap_score,
+                    ..left
+                };
+            }
+            if cell.score <= 0 {
+                cell = empty;
+            } else if cell.score >= min_score {
+                let alignment = best
+                    .entry((cell.start_a, cell.start_b))
+                    .or_insert(Alignment {
+                        score: 0,
+                        start_a: cell.start_a,
+                        end_a: i,
+                        start_b: cell.start_b,
+                        end_b: j,
+                    });
+                if cell.score > alignment.score {
+                    alignment.score = cell.score;
+                    alignment.end_a = i;
+                    alignment.end_b = j;
+                }
+            }
+            h[i][j] = cell;
+        }
+    }
+
+    let mut candidates: Vec<Alignment> = best.into_values().collect();
+    candidates.sort_unstable_by(|x, y| {
+        y.score
+            .cmp(&x.score)
+            .then(x.start_a.cmp(&y.start_a))
+            .then(x.start_b.cmp(&y.start_b))
+    });
+    let mut accepted: Vec<Alignment> = Vec::new();
+    let mut ret: Vec<SubstringResult> = Vec::new();
+    let mut dropped = 0;
+    let total = candidates.len();
+    for (i, candidate) in candidates.into_iter().enumerate() {
+        if ret.len() >= config.max_substrings {
+            dropped = total - i;
+            break;
+        }
+        if accepted.iter().any(|x| x.overlaps(&candidate)) {
+            continue;
+        }
+        // Alignments that are too short once trimmed leave the tokens to the ones they overlap
+        let Some(trimmed) = trim_to_ratio(a, b, costs, &candidate, config.ratio, min_len) else {
+            continue;
+        };
+        accepted.push(Alignment {
+            end_a: trimmed.end_a,
+            end_b: trimmed.end_b,
+            ..candidate
+        });
+        ret.push(trimmed);
+    }
+    ret.sort_unstable_by_key(|x| x.start_a);
+    Ok(Matches { results: ret, dropped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::char_tokens;
+
+    #[test]
+    fn aligns_through_a_mismatch() {
+        let a = char_tokens("xyzabcdXfghuvw");
+        let b = char_tokens("qrabcdYfghst");
+        let config = AnalysisConfig {
+            min_length: 4,
+            ..AnalysisConfig::default()
+        };
+        let matches = find_local_alignments(&a, &b, &EditCosts::default(), &config, &mut Monitor::default()).unwrap();
+        let found: Vec<_> = matches.results.iter().map(|x| (x.start_a, x.end_a, x.start_b, x.end_b)).collect();
+        // abcd and fgh score more together than abcd alone, despite X against Y
+        assert_eq!(found, [(3, 11, 2, 10)]);
+        assert_eq!(matches.results[0].len, 8);
+        assert_eq!(matches.dropped, 0);
+    }
+
+    #[test]
+    fn keeps_alignments_that_pay_for_their_edits() {
+        let a = char_tokens("QQQQQabcXefgYijRRRRR");
+        let b = char_tokens("TTTTTabcWefgZijUUUUU");
+        let config = AnalysisConfig {
+            min_length: 10,
+            ratio: 0.8,
+            ..AnalysisConfig::default()
+        };
+        let matches = find_local_alignments(&a, &b, &EditCosts::default(), &config, &mut Monitor::default()).unwrap();
+        let found: Vec<_> = matches.results.iter().map(|x| (x.start_a, x.end_a, x.start_b, x.end_b)).collect();
+        // Eight matches and two mismatches score 14, less than the match score for every token at the ratio
+        assert_eq!(found, [(5, 15, 5, 15)]);
+        assert_eq!(matches.results[0].edit_ratio, 0.8);
+    }
+
+    #[test]
+    fn alignments_are_trimmed_back_to_the_ratio() {
+        let a = char_tokens("QQQQabcdefghijklmnXXopqrSSSS");
+        let b = char_tokens("TTTTabcdefghijklmnopqrVVVV");
+        let found = |ratio| {
+            let config = AnalysisConfig {
+                ratio,
+                ..AnalysisConfig::default()
+            };
+            let matches = find_local_alignments(&a, &b, &EditCosts::default(), &config, &mut Monitor::default()).unwrap();
+            assert!(matches.results.iter().all(|x| x.edit_ratio >= ratio), "{:?}", matches.results);
+            matches.results.iter().map(|x| (x.start_a, x.end_a, x.edit_ratio)).collect::<Vec<_>>()
+        };
+        // The gap costs less than opqr scores, but leaves the whole at a ratio of 0.9
+        assert_eq!(found(0.9), [(4, 24, 0.9)]);
+        assert_eq!(found(0.95), [(4, 18, 1.0)]);
+    }
+}
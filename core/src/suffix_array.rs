@@ -0,0 +1,394 @@
+/*
+* Finds all maximal exact matches between two texts using a generalized suffix
+* array with an LCP array over the concatenation A + separator + B.
+* Construction is O(n log n) by prefix doubling, enumeration is output sensitive.
+*/
+use crate::{
+    SelfComparison,
+    config::AnalysisConfig,
+    costs::EditCosts,
+    error::Error,
+    progress::Monitor,
+    synonyms::Token,
+    utils::{self, Matches, SubstringResult},
+};
+
+// Token keys are already small integers, so they serve directly as the alphabet
+fn concatenate_tokens(a: &[Token], b: &[Token]) -> Vec<u32> {
+    let mut text = Vec::with_capacity(a.len() + b.len() + 1);
+    text.extend(a.iter().map(|t| t.key));
+    // The separator is unique, so no common prefix can run from A into B
+    text.push(u32::MAX);
+    text.extend(b.iter().map(|t| t.key));
+    text
+}
+
+fn build_suffix_array(text: &[u32]) -> (Vec<usize>, Vec<usize>) {
+    let n = text.len();
+    let mut sa: Vec<usize> = (0..n).collect();
+    sa.sort_unstable_by_key(|&i| text[i]);
+    let mut rank = vec![0; n];
+    for i in 1..n {
+        rank[sa[i]] = rank[sa[i - 1]] + usize::from(text[sa[i]] != text[sa[i - 1]]);
+    }
+
+    let mut k = 1;
+    let mut second = Vec::with_capacity(n);
+    let mut sorted = vec![0; n];
+    let mut new_rank = vec![0; n];
+    while n > 0 && rank[sa[n - 1]] < n - 1 {
+        // Order by the rank k positions ahead, suffixes running off the end come first
+        second.clear();
+        second.extend(n.saturating_sub(k)..n);
+        second.extend(sa.iter().filter(|&&p| p >= k).map(|&p| p - k));
+
+        // Stable counting sort by the rank of the first half
+        let mut count = vec![0; n + 1];
+        for &p in &second {
+            count[rank[p] + 1] += 1;
+        }
+        for i in 1..(n + 1) {
+            count[i] += count[i - 1];
+        }
+        for &p in &second {
+            sorted[count[rank[p]]] = p;
+            count[rank[p]] += 1;
+        }
+        std::mem::swap(&mut sa, &mut sorted);
+
+        let key = |p: usize| (rank[p], if p + k < n { rank[p + k] + 1 } else { 0 });
+        new_rank[sa[0]] = 0;
+        for i in 1..n {
+            new_rank[sa[i]] = new_rank[sa[i - 1]] + usize::from(key(sa[i]) != key(sa[i - 1]));
+        }
+        std::mem::swap(&mut rank, &mut new_rank);
+        k *= 2;
+    }
+    (sa, rank)
+}
+
+// Kasai's algorithm: lcp[i] is the longest common prefix of sa[i - 1] and sa[i]
+fn build_lcp(text: &[u32], sa: &[usize], rank: &[usize]) -> Vec<usize> {
+    let n = text.len();
+    let mut lcp = vec![0; n];
+    let mut h: usize = 0;
+    for i in 0..n {
+        if rank[i] == 0 {
+            h = 0;
+            continue;
+        }
+        let j = sa[rank[i] - 1];
+        while i + h < n && j + h < n && text[i + h] == text[j + h] {
+            h += 1;
+        }
+        lcp[rank[i]] = h;
+        h = h.saturating_sub(1);
+    }
+    lcp
+}
+
+// Start in A, start in B and length of a maximal exact match
+type Mem = (usize, usize, usize);
+
+// Suffixes that share a prefix, split by the text they start in, since only pairs
+// across the texts can be matches. Positions are in the concatenated text.
+#[derive(Default)]
+struct Group {
+    a: Vec<usize>,
+    b: Vec<usize>,
+}
+
+impl Group {
+    fn len(&self) -> usize {
+        self.a.len() + self.b.len()
+    }
+
+    fn push(&mut self, position: usize, len_a: usize) {
+        if position < len_a {
+            self.a.push(position);
+        } else {
+            self.b.push(position);
+        }
+    }
+
+    fn append(&mut self, other: &mut Group) {
+        self.a.append(&mut other.a);
+        self.b.append(&mut other.b);
+    }
+
+    fn clear(&mut self) {
+        self.a.clear();
+        self.b.clear();
+    }
+}
+
+struct Mems<'a> {
+    text: &'a [u32],
+    len_a: usize,
+    self_comparison: SelfComparison,
+    max_substrings: usize,
+    found: Vec<Mem>,
+    dropped: usize,
+}
+
+impl Mems<'_> {
+    fn is_full(&self) -> bool {
+        self.found.len() >= self.max_substrings
+    }
+
+    // Every pair between the two groups shares exactly `len` tokens
+    fn emit(&mut self, group: &Group, other: &Group, len: usize) {
+        if self.dropped > 0 {
+            // The group that found the search full has counted what it left
+            return;
+        }
+        let pairs = group.a.len() * other.b.len() + other.a.len() * group.b.len();
+        let mut done = 0;
+        for (positions_a, positions_b) in [(&group.a, &other.b), (&other.a, &group.b)] {
+            // Walking the positions of one text when the other has none would be quadratic on repetitive texts
+            if positions_b.is_empty() {
+                continue;
+            }
+            for &pos_a in positions_a {
+                for &pos_b in positions_b {
+                    done += 1;
+                    // Only keep left maximal matches, the others are contained in one of these
+                    if pos_a > 0 && pos_b > self.len_a + 1 && self.text[pos_a - 1] == self.text[pos_b - 1] {
+                        continue;
+                    }
+                    let pos_b = pos_b - self.len_a - 1;
+                    // A repeat running into its own copy is cut short where the copy starts
+                    let Some(max_len) = self.self_comparison.max_len(pos_a, pos_b) else {
+                        continue;
+                    };
+                    if self.is_full() {
+                        // Not every remaining pair is a match, but each one is a candidate
+                        self.dropped = pairs - done + 1;
+                        return;
+                    }
+                    self.found.push((pos_a, pos_b, len.min(max_len)));
+                }
+            }
+        }
+    }
+}
+
+fn find_maximal_matches(
+    text: &[u32],
+    len_a: usize,
+    min_len: usize,
+    self_comparison: SelfComparison,
+    max_substrings: usize,
+    monitor: &mut Monitor,
+) -> Result<(Vec<Mem>, usize), Error> {
+    let (sa, rank) = build_suffix_array(text);
+    let lcp = build_lcp(text, &sa, &rank);
+    let mut mems = Mems {
+        text,
+        len_a,
+        self_comparison,
+        max_substrings,
+        found: Vec::new(),
+        dropped: 0,
+    };
+
+    // Each entry holds suffixes sharing exactly `lcp` tokens with every suffix after them,
+    // with strictly increasing lcp from the bottom of the stack to the top
+    let mut stack: Vec<(usize, Group)> = Vec::new();
+    let mut current = Group::default();
+    current.push(sa[0], len_a);
+    for i in 1..(sa.len() + 1) {
+        // Enumeration is the first half of the work, extending the matches the second
+        monitor.report(i as f32 / sa.len() as f32 / 2.0, mems.found.len())?;
+        let h = lcp.get(i).copied().unwrap_or(0);
+        while let Some((depth, _)) = stack.last() {
+            if *depth <= h {
+                break;
+            }
+            let (depth, mut group) = stack.pop().unwrap();
+            mems.emit(&group, &current, depth);
+            if group.len() < current.len() {
+                std::mem::swap(&mut group, &mut current);
+            }
+            group.append(&mut current);
+            current = group;
+        }
+        if h < min_len {
+            // Nothing further down the array shares enough with these suffixes
+            current.clear();
+        } else if let Some((depth, group)) = stack.last_mut().filter(|(depth, _)| *depth == h) {
+            mems.emit(group, &current, *depth);
+            group.append(&mut current);
+        } else {
+            stack.push((h, std::mem::take(&mut current)));
+        }
+        // Once full, the search goes on to the next candidate only to count what is left
+        if mems.dropped > 0 {
+            break;
+        }
+        if let Some(&next) = sa.get(i) {
+            current.push(next, len_a);
+        }
+    }
+    Ok((mems.found, mems.dropped))
+}
+
+pub fn find_exact_repeats(
+    a: &[Token],
+    b: &[Token],
+    costs: &EditCosts,
+    config: &AnalysisConfig,
+    monitor: &mut Monitor,
+) -> Result<Matches, Error> {
+    let min_len = config.min_length;
+    if a.is_empty() || b.is_empty() {
+        return Ok(Matches::default());
+    }
+    let text = concatenate_tokens(a, b);
+    let (found, dropped) =
+        find_maximal_matches(&text, a.len(), min_len.max(1), config.self_comparison, config.max_substrings, monitor)?;
+    let mut ret: Vec<SubstringResult> = Vec::with_capacity(found.len());
+    for (i, &(start_a, start_b, len)) in found.iter().enumerate() {
+        monitor.report(0.5 + i as f32 / found.len() as f32 / 2.0, ret.len())?;
+        let mut ma = SubstringResult {
+            start_a,
+            end_a: start_a + len,
+            start_b,
+            end_b: start_b + len,
+            len: utils::find_length_from_tokens(&a[start_a..start_a + len], &b[start_b..start_b + len]),
+            edit_ratio: 1.0,
+        };
+        if config.extend_repeats {
+            // With no strikes allowed this leaves the exact match untouched
+            utils::expand_match_left_and_right(
+                &mut ma,
+                a,
+                b,
+                costs,
+                config.ratio,
+                config.max_strikes,
+                config.self_comparison,
+                monitor.cancellation(),
+            );
+        }
+        if ma.len >= min_len {
+            ret.push(ma);
+        }
+    }
+    // Different exact matches can grow into the same fuzzy match
+    ret.sort_unstable_by_key(|x| (x.start_a, x.start_b, x.end_a, x.end_b));
+    ret.dedup_by_key(|x| (x.start_a, x.start_b, x.end_a, x.end_b));
+    Ok(Matches { results: ret, dropped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Warning,
+        test_utils::{Random, char_tokens, tokens},
+    };
+
+    // Every pair of starts that cannot be extended to the left, with the length they share
+    fn brute_force(a: &[u32], b: &[u32], min_len: usize) -> Vec<Mem> {
+        let mut found = Vec::new();
+        for i in 0..a.len() {
+            for j in 0..b.len() {
+                if i > 0 && j > 0 && a[i - 1] == b[j - 1] {
+                    continue;
+                }
+                let len = a[i..].iter().zip(&b[j..]).take_while(|(x, y)| x == y).count();
+                if len >= min_len {
+                    found.push((i, j, len));
+                }
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn maximal_matches_equal_brute_force() {
+        let mut random = Random::new(0xd1b54a32d192ed03);
+        for _ in 0..300 {
+            let (a, b) = (random.keys(40, 3), random.keys(40, 3));
+            if a.is_empty() || b.is_empty() {
+                continue;
+            }
+            let min_len = 1 + random.below(4) as usize;
+            let text = concatenate_tokens(&tokens(&a), &tokens(&b));
+            let (mut found, dropped) =
+                find_maximal_matches(&text, a.len(), min_len, SelfComparison::Off, usize::MAX, &mut Monitor::default())
+                    .unwrap();
+            found.sort_unstable();
+            assert_eq!(found, brute_force(&a, &b, min_len), "{:?} {:?} {}", a, b, min_len);
+            assert_eq!(dropped, 0);
+        }
+    }
+
+    // Chars out of a range of common ideographs, so that matches by chance stay short
+    fn text(random: &mut Random, len: usize) -> String {
+        (0..len).map(|_| char::from_u32(0x4e00 + random.below(3000) as u32).unwrap()).collect()
+    }
+
+    #[test]
+    fn long_shared_passages_take_near_linear_time() {
+        let mut random = Random::new(5);
+        let passage = text(&mut random, 50_000);
+        let a = char_tokens(&(text(&mut random, 25_000) + &passage + &text(&mut random, 25_000)));
+        let b = char_tokens(&(text(&mut random, 10_000) + &passage + &text(&mut random, 40_000)));
+        let config = AnalysisConfig::default();
+        let started = std::time::Instant::now();
+        let matches = find_exact_repeats(&a, &b, &EditCosts::default(), &config, &mut Monitor::default()).unwrap();
+        // Growing the match through edits took minutes on a passage this long
+        assert!(started.elapsed().as_secs() < 20, "{:?}", started.elapsed());
+        let passage = matches.results.iter().max_by_key(|x| x.end_a - x.start_a).unwrap();
+        assert_eq!((passage.start_a, passage.end_a, passage.start_b, passage.end_b), (25_000, 75_000, 10_000, 60_000));
+        assert_eq!(passage.edit_ratio, 1.0);
+    }
+
+    #[test]
+    fn exact_matches_only_grow_when_asked_to() {
+        let a = "天地玄黃宇宙洪荒日月盈昃辰宿列張寒來暑往秋收冬藏";
+        let b = "天地玄黃宇宙洪荒日月盈仄辰宿列張寒來暑往秋收冬藏";
+        let matches = |extend_repeats| {
+            let config = AnalysisConfig {
+                algorithm: crate::Algorithm::SuffixArray,
+                extend_repeats,
+                ..AnalysisConfig::default()
+            };
+            let response = crate::analyze(a, b, Vec::new(), Vec::new(), &config).unwrap();
+            let matches = response.result.iter().filter(|x| x.levenshtein_match);
+            matches.map(|x| (x.a.start, x.a.end, x.similarity)).collect::<Vec<_>>()
+        };
+        // The exact matches stop at the differing char, grown they run through it
+        assert_eq!(matches(false), [(0, 11, 1.0), (12, 24, 1.0)]);
+        assert_eq!(matches(true), [(0, 24, 23.0 / 24.0)]);
+    }
+
+    #[test]
+    fn the_limit_warns_once_a_match_is_left_out() {
+        let a = "天地玄黃宇宙洪荒日月盈昃辰宿列張寒來暑往秋收冬藏";
+        let b = "天地玄黃宇宙洪荒日月盈仄辰宿列張寒來暑往秋收冬藏";
+        let warnings = |algorithm, max_substrings| {
+            let config = AnalysisConfig {
+                algorithm,
+                max_substrings,
+                ..AnalysisConfig::default()
+            };
+            crate::analyze(a, b, Vec::new(), Vec::new(), &config).unwrap().warnings
+        };
+        // Like every algorithm, it warns when the limit leaves no room for any match
+        for algorithm in [
+            crate::Algorithm::Comparativus,
+            crate::Algorithm::SmithWaterman,
+            crate::Algorithm::GreedyStringTiling,
+            crate::Algorithm::SuffixArray,
+        ] {
+            let warnings = warnings(algorithm, 0);
+            assert!(matches!(warnings[..], [Warning::MaxSubstringsReached { limit: 0, .. }]), "{:?}", algorithm);
+        }
+        let suffix_array = |max_substrings| warnings(crate::Algorithm::SuffixArray, max_substrings);
+        assert_eq!(suffix_array(1), [Warning::MaxSubstringsReached { limit: 1, dropped: 1 }]);
+        assert!(suffix_array(2).is_empty());
+    }
+}
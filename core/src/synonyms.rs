@@ -0,0 +1,454 @@
+use std::{borrow::Cow, hash::Hash};
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Error, utils::Substring, variants::VariantTable};
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Word {
+    pub start: usize,
+    pub end: usize,
+    // For a linked word, what substituting it for the synonym's word costs: from 0, the
+    // default, for a true variant up to 1 for a word that is no closer than any other
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f32>,
+}
+
+impl Word {
+    pub fn new(start: usize, end: usize) -> Self {
+        Word { start, end, cost: None }
+    }
+
+    pub fn with_cost(self, cost: f32) -> Self {
+        Word {
+            cost: Some(cost),
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Synonym {
+    pub word: Word,
+    pub synonyms: Vec<Word>,
+}
+
+impl Synonym {
+    pub fn new(word: Word, synonyms: Vec<Word>) -> Self {
+        Synonym { word, synonyms }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Token {
+    pub start: usize,
+    pub end: usize,
+    // Tokens are equal exactly when their keys are, see `Vocabulary`
+    pub key: u32,
+    // What the token counts for in match lengths and ratios: its chars, or 1 for a word
+    pub size: usize,
+}
+
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Token {}
+
+impl Hash for Token {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl Token {
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+// Hands out the equivalence class ids used as token keys. Plain tokens share a
+// class with every token of the same text, after mapping variant characters to
+// their canonical form. Linked synonyms merge the classes of their texts.
+#[derive(Default)]
+pub struct Vocabulary<'a> {
+    forms: FxHashMap<Cow<'a, [char]>, u32>,
+    variants: Option<&'a VariantTable>,
+    classes: u32,
+}
+
+impl<'a> Vocabulary<'a> {
+    pub fn with_variants(variants: &'a VariantTable) -> Self {
+        Vocabulary {
+            variants: Some(variants).filter(|v| !v.is_empty()),
+            ..Vocabulary::default()
+        }
+    }
+
+    pub fn intern(&mut self, text: &'a [char]) -> u32 {
+        let form = match self.variants {
+            Some(variants) if text.iter().any(|&c| variants.canonical(c) != c) => {
+                Cow::Owned(text.iter().map(|&c| variants.canonical(c)).collect())
+            }
+            _ => Cow::Borrowed(text),
+        };
+        if let Some(&class) = self.forms.get(form.as_ref()) {
+            return class;
+        }
+        let class = self.fresh();
+        self.forms.insert(form, class);
+        class
+    }
+
+    // A token over text[start..end], keyed by its synonym class if the span is a linked word
+    pub fn token(&mut self, text: &'a [char], linked: &LinkedWords, start: usize, end: usize, size: usize) -> Token {
+        let key = match linked.get(&(start, end)) {
+            Some(&class) => class,
+            None => self.intern(&text[start..end]),
+        };
+        Token { start, end, key, size }
+    }
+
+    pub fn fresh(&mut self) -> u32 {
+        self.classes += 1;
+        self.classes - 1
+    }
+
+    // Moves the forms of every class that is a key of `merged` into the class it maps to
+    pub fn merge(&mut self, merged: &FxHashMap<u32, u32>) {
+        for class in self.forms.values_mut() {
+            if let Some(&into) = merged.get(class) {
+                *class = into;
+            }
+        }
+    }
+}
+
+// Checks that every word lies within its text and that the words of one text,
+// which must already be sorted by start, do not overlap
+pub fn validate_synonyms(synonyms: &[Synonym], text_len: usize, other_len: usize) -> Result<(), Error> {
+    let check = |word: &Word, len: usize| {
+        if word.start >= word.end || word.end > len {
+            return Err(Error::MalformedSynonym {
+                reason: format!(
+                    "word {}..{} does not fit in a text of {} characters",
+                    word.start, word.end, len
+                ),
+            });
+        }
+        Ok(())
+    };
+    for synonym in synonyms {
+        check(&synonym.word, text_len)?;
+        for other in &synonym.synonyms {
+            check(other, other_len)?;
+            if let Some(cost) = other.cost.filter(|cost| !(0.0..=1.0).contains(cost)) {
+                return Err(Error::MalformedSynonym {
+                    reason: format!(
+                        "word {}..{} has a cost of {}, which is not between 0 and 1",
+                        other.start, other.end, cost
+                    ),
+                });
+            }
+        }
+    }
+    for pair in synonyms.windows(2) {
+        if pair[1].word.start < pair[0].word.end {
+            return Err(Error::MalformedSynonym {
+                reason: format!(
+                    "words {}..{} and {}..{} overlap",
+                    pair[0].word.start, pair[0].word.end, pair[1].word.start, pair[1].word.end
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Checks that the words the synonyms of one text link to, which become tokens of the other
+// text, do not partly overlap each other or the words of that text
+pub fn validate_linked_words(synonyms: &[Synonym], other: &[Synonym]) -> Result<(), Error> {
+    let linked = synonyms.iter().flat_map(|synonym| &synonym.synonyms);
+    let mut words: Vec<(usize, usize)> =
+        linked.chain(other.iter().map(|synonym| &synonym.word)).map(|word| (word.start, word.end)).collect();
+    words.sort_unstable();
+    words.dedup();
+    for pair in words.windows(2) {
+        if pair[1].0 < pair[0].1 {
+            return Err(Error::MalformedSynonym {
+                reason: format!(
+                    "words {}..{} and {}..{} overlap in the text they are linked into",
+                    pair[0].0, pair[0].1, pair[1].0, pair[1].1
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub type LinkedWords = FxHashMap<(usize, usize), u32>;
+
+// The words of both texts that were resolved into one class, so that they all match each other
+#[derive(Clone, Debug, Serialize)]
+pub struct SynonymClass {
+    pub a: Vec<Substring>,
+    pub b: Vec<Substring>,
+}
+
+// Gathers the words of every class, in order of their first word in A, or in B for classes with none in A
+pub fn synonym_classes(linked_a: &LinkedWords, linked_b: &LinkedWords) -> Vec<SynonymClass> {
+    let mut classes: FxHashMap<u32, SynonymClass> = FxHashMap::default();
+    for (in_b, linked) in [(false, linked_a), (true, linked_b)] {
+        for (&(start, end), &class) in linked {
+            let words = classes.entry(class).or_insert_with(|| SynonymClass { a: Vec::new(), b: Vec::new() });
+            let words = if in_b { &mut words.b } else { &mut words.a };
+            words.push(Substring { start, end });
+        }
+    }
+    // A word left on its own, like a dictionary form whose equivalents do not occur, matches nothing new
+    let mut classes: Vec<SynonymClass> =
+        classes.into_values().filter(|class| class.a.len() + class.b.len() > 1).collect();
+    for class in classes.iter_mut() {
+        class.a.sort_unstable_by_key(|word| word.start);
+        class.b.sort_unstable_by_key(|word| word.start);
+    }
+    classes.sort_unstable_by_key(|class| (class.a.is_empty(), class.a.first().or(class.b.first()).map(|word| word.start)));
+    classes
+}
+
+fn find_root(parents: &mut [usize], mut node: usize) -> usize {
+    while parents[node] != node {
+        parents[node] = parents[parents[node]];
+        node = parents[node];
+    }
+    node
+}
+
+// Resolves the positional synonyms of both texts into classes, keyed by the (start, end)
+// of the linked words in A and in B respectively. A link registered on either side binds
+// both words, and words linked through a common word end up in the same class. Every
+// word stays bound to the other words with its text, so the forms of a class are merged
+// in the vocabulary. The dictionary occurrences, see `Dictionary::find`, are bound to the
+// node of their form. Links with a cost do not bind anything: they only make substituting
+// one word for the other cheaper, and are returned as substitutions between the classes.
+#[allow(clippy::too_many_arguments)]
+pub fn resolve_synonyms<'a>(
+    text_a: &'a [char],
+    text_b: &'a [char],
+    synonyms_a: &[Synonym],
+    synonyms_b: &[Synonym],
+    occurrences_a: &[(Word, usize)],
+    occurrences_b: &[(Word, usize)],
+    dictionary_links: &[(usize, usize, f32)],
+    vocabulary: &mut Vocabulary<'a>,
+) -> (LinkedWords, LinkedWords, Vec<(u32, u32, f32)>) {
+    // Nodes are the linked words, A words are stored as (false, start, end) and B words as (true, start, end).
+    // The first nodes are the ones of the dictionary, which numbers them itself.
+    let dictionary_nodes = occurrences_a
+        .iter()
+        .chain(occurrences_b)
+        .map(|&(_, x)| x + 1)
+        .chain(dictionary_links.iter().map(|&(x, y, _)| x.max(y) + 1))
+        .max()
+        .unwrap_or(0);
+    let mut nodes: FxHashMap<(bool, usize, usize), usize> = FxHashMap::default();
+    let mut parents: Vec<usize> = (0..dictionary_nodes).collect();
+    let mut node = |nodes: &mut FxHashMap<(bool, usize, usize), usize>, key: (bool, usize, usize)| {
+        *nodes.entry(key).or_insert_with(|| {
+            parents.push(parents.len());
+            parents.len() - 1
+        })
+    };
+    let mut links: Vec<(usize, usize, f32)> = dictionary_links.to_vec();
+    for (in_b, synonyms) in [(false, synonyms_a), (true, synonyms_b)] {
+        for synonym in synonyms.iter().filter(|s| !s.synonyms.is_empty()) {
+            let word = node(&mut nodes, (in_b, synonym.word.start, synonym.word.end));
+            for other in &synonym.synonyms {
+                let other_node = node(&mut nodes, (!in_b, other.start, other.end));
+                links.push((word, other_node, other.cost.unwrap_or(0.0)));
+            }
+        }
+    }
+    for (in_b, occurrences) in [(false, occurrences_a), (true, occurrences_b)] {
+        for &(word, form) in occurrences {
+            links.push((node(&mut nodes, (in_b, word.start, word.end)), form, 0.0));
+        }
+    }
+    // The interned texts of the words are nodes as well, keyed by their token key
+    let mut forms: FxHashMap<u32, usize> = FxHashMap::default();
+    for (&(in_b, start, end), &word) in &nodes {
+        let text = if in_b { text_b } else { text_a };
+        let form = *forms.entry(vocabulary.intern(&text[start..end])).or_insert_with(|| {
+            parents.push(parents.len());
+            parents.len() - 1
+        });
+        links.push((word, form, 0.0));
+    }
+    for &(x, y, cost) in &links {
+        if cost == 0.0 {
+            let root_x = find_root(&mut parents, x);
+            let root_y = find_root(&mut parents, y);
+            parents[root_x] = root_y;
+        }
+    }
+
+    // Every word has its form in its class, which takes the lowest key of its forms
+    let mut classes: FxHashMap<usize, u32> = FxHashMap::default();
+    for (&form, &node) in &forms {
+        let class = classes.entry(find_root(&mut parents, node)).or_insert(form);
+        *class = (*class).min(form);
+    }
+    let merged: FxHashMap<u32, u32> = forms
+        .iter()
+        .map(|(&form, &node)| (form, classes[&find_root(&mut parents, node)]))
+        .filter(|(form, class)| form != class)
+        .collect();
+    vocabulary.merge(&merged);
+    let mut linked_a = LinkedWords::default();
+    let mut linked_b = LinkedWords::default();
+    for ((in_b, start, end), node) in nodes {
+        let class = classes[&find_root(&mut parents, node)];
+        if in_b {
+            linked_b.insert((start, end), class);
+        } else {
+            linked_a.insert((start, end), class);
+        }
+    }
+    // Only links between words that occur in the texts have a class on both ends
+    let substitutions = links
+        .into_iter()
+        .filter(|&(_, _, cost)| cost > 0.0)
+        .filter_map(|(x, y, cost)| {
+            let class_x = *classes.get(&find_root(&mut parents, x))?;
+            let class_y = *classes.get(&find_root(&mut parents, y))?;
+            Some((class_x, class_y, cost))
+        })
+        .collect();
+    (linked_a, linked_b, substitutions)
+}
+
+// The classes of the dictionary occurrences of any number of texts without positional
+// synonyms, as `resolve_synonyms` would find them: every occurrence is bound to the class
+// of its form, and the links between forms become substitutions between their classes.
+pub fn resolve_occurrences(
+    occurrences: &[Vec<(Word, usize)>],
+    dictionary_links: &[(usize, usize, f32)],
+    vocabulary: &mut Vocabulary,
+) -> (Vec<LinkedWords>, Vec<(u32, u32, f32)>) {
+    let mut classes: FxHashMap<usize, u32> = FxHashMap::default();
+    let linked = occurrences
+        .iter()
+        .map(|occurrences| {
+            occurrences
+                .iter()
+                .map(|&(word, form)| ((word.start, word.end), *classes.entry(form).or_insert_with(|| vocabulary.fresh())))
+                .collect()
+        })
+        .collect();
+    let substitutions = dictionary_links
+        .iter()
+        .filter_map(|&(x, y, cost)| Some((*classes.get(&x)?, *classes.get(&y)?, cost)))
+        .collect();
+    (linked, substitutions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(synonyms_a: &[Synonym], synonyms_b: &[Synonym]) -> (LinkedWords, LinkedWords, Vec<(u32, u32, f32)>) {
+        // Every char differs, so only the links bind anything
+        let text_a: Vec<char> = "abcdefghij".chars().collect();
+        let text_b: Vec<char> = "klmnopqrst".chars().collect();
+        let mut vocabulary = Vocabulary::default();
+        resolve_synonyms(&text_a, &text_b, synonyms_a, synonyms_b, &[], &[], &[], &mut vocabulary)
+    }
+
+    fn spans(words: &[Substring]) -> Vec<(usize, usize)> {
+        words.iter().map(|word| (word.start, word.end)).collect()
+    }
+
+    #[test]
+    fn links_bind_both_ways() {
+        let from_a = resolve(&[Synonym::new(Word::new(0, 1), vec![Word::new(2, 4)])], &[]);
+        let from_b = resolve(&[], &[Synonym::new(Word::new(2, 4), vec![Word::new(0, 1)])]);
+        for (linked_a, linked_b, substitutions) in [from_a, from_b] {
+            assert_eq!(linked_a[&(0, 1)], linked_b[&(2, 4)]);
+            assert!(substitutions.is_empty());
+            let classes = synonym_classes(&linked_a, &linked_b);
+            assert_eq!(classes.len(), 1);
+            assert_eq!(spans(&classes[0].a), [(0, 1)]);
+            assert_eq!(spans(&classes[0].b), [(2, 4)]);
+        }
+    }
+
+    #[test]
+    fn links_through_a_common_word_join_classes() {
+        // A 0..1 and A 5..6 both link to B 3..4, and B 7..8 links back to A 5..6
+        let synonyms_a = [
+            Synonym::new(Word::new(0, 1), vec![Word::new(3, 4)]),
+            Synonym::new(Word::new(5, 6), vec![Word::new(3, 4)]),
+        ];
+        let synonyms_b = [Synonym::new(Word::new(7, 8), vec![Word::new(5, 6)])];
+        let (linked_a, linked_b, _) = resolve(&synonyms_a, &synonyms_b);
+        let class = linked_a[&(0, 1)];
+        assert_eq!(linked_a[&(5, 6)], class);
+        assert_eq!(linked_b[&(3, 4)], class);
+        assert_eq!(linked_b[&(7, 8)], class);
+        let classes = synonym_classes(&linked_a, &linked_b);
+        assert_eq!(classes.len(), 1);
+        assert_eq!(spans(&classes[0].a), [(0, 1), (5, 6)]);
+        assert_eq!(spans(&classes[0].b), [(3, 4), (7, 8)]);
+    }
+
+    #[test]
+    fn weighted_links_stay_apart() {
+        let synonyms_a = [Synonym::new(Word::new(0, 1), vec![Word::new(0, 2).with_cost(0.5)])];
+        let (linked_a, linked_b, substitutions) = resolve(&synonyms_a, &[]);
+        assert_ne!(linked_a[&(0, 1)], linked_b[&(0, 2)]);
+        assert_eq!(substitutions, [(linked_a[&(0, 1)], linked_b[&(0, 2)], 0.5)]);
+        assert!(synonym_classes(&linked_a, &linked_b).is_empty());
+    }
+
+    #[test]
+    fn linked_words_keep_matching_their_text() {
+        let text_a: Vec<char> = "學而時習之不亦說乎".chars().collect();
+        let text_b: Vec<char> = "學而時習之不亦說悅".chars().collect();
+        let synonyms_a = [Synonym::new(Word::new(7, 8), vec![Word::new(8, 9)])];
+        let mut vocabulary = Vocabulary::default();
+        let (linked_a, linked_b, _) =
+            resolve_synonyms(&text_a, &text_b, &synonyms_a, &[], &[], &[], &[], &mut vocabulary);
+        let class = linked_a[&(7, 8)];
+        assert_eq!(linked_b[&(8, 9)], class);
+        // The 說 of B that is not linked still matches the linked one of A, and every 悅 joins them
+        assert_eq!(vocabulary.intern(&text_b[7..8]), class);
+        assert_eq!(vocabulary.intern(&text_b[8..9]), class);
+        assert_ne!(vocabulary.intern(&text_a[0..1]), class);
+    }
+
+    #[test]
+    fn linked_words_may_not_overlap_the_words_of_their_text() {
+        let linked = |word: Word| [Synonym::new(Word::new(0, 1), vec![word])];
+        let own = [Synonym::new(Word::new(1, 3), Vec::new())];
+        assert!(validate_linked_words(&linked(Word::new(0, 2)), &own).is_err());
+        assert!(validate_linked_words(&linked(Word::new(1, 3)), &own).is_ok());
+        assert!(validate_linked_words(&linked(Word::new(3, 4)), &own).is_ok());
+        let crossing = [
+            Synonym::new(Word::new(0, 1), vec![Word::new(2, 5)]),
+            Synonym::new(Word::new(1, 2), vec![Word::new(4, 6)]),
+        ];
+        assert!(validate_linked_words(&crossing, &[]).is_err());
+        let shared = [
+            Synonym::new(Word::new(0, 1), vec![Word::new(2, 5)]),
+            Synonym::new(Word::new(1, 2), vec![Word::new(2, 5)]),
+        ];
+        assert!(validate_linked_words(&shared, &[]).is_ok());
+    }
+}
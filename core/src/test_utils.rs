@@ -0,0 +1,35 @@
+// Helpers shared by the unit tests
+use crate::synonyms::Token;
+
+// One char long token per key, the keys standing for the chars
+pub fn tokens(keys: &[u32]) -> Vec<Token> {
+    keys.iter()
+        .enumerate()
+        .map(|(i, &key)| Token { start: i, end: i + 1, key, size: 1 })
+        .collect()
+}
+
+// One token per char of the text, keyed by the char itself
+pub fn char_tokens(text: &str) -> Vec<Token> {
+    tokens(&text.chars().map(u32::from).collect::<Vec<_>>())
+}
+
+// A linear congruential generator, so that the randomized tests always see the same inputs
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random(seed)
+    }
+
+    // A number below n
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) % n
+    }
+
+    // Up to max_len keys out of the first `alphabet` ones, small alphabets giving many repeats
+    pub fn keys(&mut self, max_len: u64, alphabet: u32) -> Vec<u32> {
+        (0..self.below(max_len + 1)).map(|_| self.below(alphabet as u64) as u32).collect()
+    }
+}
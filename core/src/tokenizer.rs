@@ -0,0 +1,291 @@
+use unicode_properties::{GeneralCategoryGroup, UnicodeGeneralCategory};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    Tokenization,
+    synonyms::{LinkedWords, Synonym, Token, Vocabulary},
+    utils::Substring,
+};
+
+// Splits a text into the spans of chars that the algorithms match, in order and without
+// overlapping. Chars outside every span, like the whitespace between words, are never matched.
+pub trait Tokenizer {
+    fn tokenize(&self, text: &[char]) -> Vec<Substring>;
+
+    // What a synonym counts for in match lengths and ratios, given how many spans start inside it
+    fn synonym_size(&self, spans: usize) -> usize {
+        spans.max(1)
+    }
+}
+
+impl Tokenization {
+    pub fn tokenizer(self) -> &'static dyn Tokenizer {
+        match self {
+            Tokenization::Character => &CharTokenizer,
+            Tokenization::Grapheme => &GraphemeTokenizer,
+            Tokenization::Word => &WordTokenizer,
+        }
+    }
+}
+
+// The tokens of a text. Every synonym word, which come sorted by start and do not overlap,
+// becomes a token of its own with the key of its class, and the spans it cuts into are cut
+// short around it.
+pub(crate) fn tokenize<'a>(
+    tokenizer: &dyn Tokenizer,
+    text: &'a [char],
+    synonyms: &'a [Synonym],
+    linked: &LinkedWords,
+    vocabulary: &mut Vocabulary<'a>,
+) -> Vec<Token> {
+    let mut spans = tokenizer.tokenize(text).into_iter().filter(|span| span.start < span.end);
+    let mut tokens = Vec::new();
+    // The span up next, and whether it is the start of one rather than what a synonym left of it
+    let mut next = spans.next().map(|span| (span, true));
+    for synonym in synonyms {
+        let word = synonym.word;
+        let mut covered = 0;
+        while let Some((span, whole)) = next.filter(|(span, _)| span.start < word.end) {
+            if span.start < word.start {
+                tokens.push(vocabulary.token(text, linked, span.start, span.end.min(word.start), 1));
+            } else if whole {
+                covered += 1;
+            }
+            next = if span.end > word.end {
+                Some((Substring { start: word.end, end: span.end }, false))
+            } else {
+                spans.next().map(|span| (span, true))
+            };
+        }
+        let size = tokenizer.synonym_size(covered);
+        tokens.push(vocabulary.token(text, linked, word.start, word.end, size));
+    }
+    while let Some((span, _)) = next {
+        tokens.push(vocabulary.token(text, linked, span.start, span.end, 1));
+        next = spans.next().map(|span| (span, true));
+    }
+    tokens
+}
+
+// One token per character
+pub struct CharTokenizer;
+
+impl Tokenizer for CharTokenizer {
+    fn tokenize(&self, text: &[char]) -> Vec<Substring> {
+        (0..text.len()).map(|i| Substring { start: i, end: i + 1 }).collect()
+    }
+}
+
+// Whether a grapheme cluster starts at each char offset, including the end of the text
+pub fn grapheme_boundaries(text: &[char]) -> Vec<bool> {
+    let mut boundaries = vec![false; text.len() + 1];
+    let mut offset = 0;
+    for grapheme in text.iter().collect::<String>().graphemes(true) {
+        boundaries[offset] = true;
+        offset += grapheme.chars().count();
+    }
+    boundaries[text.len()] = true;
+    boundaries
+}
+
+// One token per user-perceived character, so that combining marks, variation selectors and
+// emoji sequences stay with their base. Synonyms count one per cluster they cover.
+pub struct GraphemeTokenizer;
+
+impl Tokenizer for GraphemeTokenizer {
+    fn tokenize(&self, text: &[char]) -> Vec<Substring> {
+        let boundaries = grapheme_boundaries(text);
+        let starts: Vec<usize> = (0..=text.len()).filter(|&i| boundaries[i]).collect();
+        starts.windows(2).map(|pair| Substring { start: pair[0], end: pair[1] }).collect()
+    }
+}
+
+fn is_word_separator(c: char) -> bool {
+    c.is_whitespace() || c.general_category_group() == GeneralCategoryGroup::Punctuation
+}
+
+// Splits the text into words at whitespace and punctuation, which are not part of any token.
+// Every word counts as one, and so does a synonym even when it spans several words.
+pub struct WordTokenizer;
+
+impl Tokenizer for WordTokenizer {
+    fn tokenize(&self, text: &[char]) -> Vec<Substring> {
+        let mut spans = Vec::new();
+        let mut i = 0;
+        while i < text.len() {
+            if is_word_separator(text[i]) {
+                i += 1;
+            } else {
+                let end = (i..text.len()).find(|&j| is_word_separator(text[j])).unwrap_or(text.len());
+                spans.push(Substring { start: i, end });
+                i = end;
+            }
+        }
+        spans
+    }
+
+    fn synonym_size(&self, _spans: usize) -> usize {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter;
+    use crate::{
+        Analyzer, Corpus, Monitor, ResponseAndOverall, Tokenization,
+        config::AnalysisConfig,
+        normalize::{CharClass, Normalization, UnicodeForm},
+        synonyms::Word,
+    };
+
+    fn spans(tokens: &[Token]) -> Vec<(usize, usize)> {
+        tokens.iter().map(|token| (token.start, token.end)).collect()
+    }
+
+    #[test]
+    fn words_are_char_spans_between_separators() {
+        let text: Vec<char> = "Ἐν ἀρχῇ ἦν ὁ λόγος, καὶ ὁ λόγος".chars().collect();
+        let mut vocabulary = Vocabulary::default();
+        let tokens = tokenize(&WordTokenizer, &text, &[], &LinkedWords::default(), &mut vocabulary);
+        assert_eq!(spans(&tokens), [(0, 2), (3, 7), (8, 10), (11, 12), (13, 18), (20, 23), (24, 25), (26, 31)]);
+        // The same word gets the same key wherever it is
+        assert_eq!(tokens[4], tokens[7]);
+        assert_ne!(tokens[4], tokens[3]);
+
+        // A synonym over two words is one token
+        let synonyms = [Synonym::new(Word::new(11, 18), Vec::new())];
+        let tokens = tokenize(&WordTokenizer, &text, &synonyms, &LinkedWords::default(), &mut vocabulary);
+        assert_eq!(spans(&tokens)[3..5], [(11, 18), (20, 23)]);
+    }
+
+    #[test]
+    fn synonyms_cut_the_spans_they_overlap() {
+        let text: Vec<char> = "the quick brown".chars().collect();
+        let synonyms = [Synonym::new(Word::new(7, 12), Vec::new())];
+        let tokens = tokenize(&WordTokenizer, &text, &synonyms, &LinkedWords::default(), &mut Vocabulary::default());
+        assert_eq!(spans(&tokens), [(0, 3), (4, 7), (7, 12), (12, 15)]);
+        assert_eq!(tokens[2].size, 1);
+
+        // A synonym counts the clusters that start inside it, not the one it cuts into
+        let text: Vec<char> = "e\u{301}abc".chars().collect();
+        let synonyms = [Synonym::new(Word::new(1, 4), Vec::new())];
+        let tokens = tokenize(&GraphemeTokenizer, &text, &synonyms, &LinkedWords::default(), &mut Vocabulary::default());
+        assert_eq!(spans(&tokens), [(0, 1), (1, 4), (4, 5)]);
+        assert_eq!(tokens[1].size, 2);
+    }
+
+    // Tibetan syllables, split at the tsheg between them
+    struct Syllables;
+
+    impl Tokenizer for Syllables {
+        fn tokenize(&self, text: &[char]) -> Vec<Substring> {
+            let mut spans = Vec::new();
+            let mut start = 0;
+            for (i, &c) in text.iter().chain(iter::once(&'་')).enumerate() {
+                if c == '་' {
+                    if start < i {
+                        spans.push(Substring { start, end: i });
+                    }
+                    start = i + 1;
+                }
+            }
+            spans
+        }
+    }
+
+    #[test]
+    fn tokenizers_of_our_own_run_end_to_end() {
+        let syllables_a = ["བཀྲ", "ཤིས", "བདེ", "ལེགས", "ཕུན", "སུམ", "ཚོགས", "རྟག", "ཏུ", "ཐོབ"];
+        let mut syllables_b = syllables_a;
+        syllables_b[2] = "སྐྱིད";
+        let (a, b) = (syllables_a.join("་"), syllables_b.join("་"));
+        // The char offsets of the third syllable
+        let third = |syllables: &[&str]| {
+            let start = syllables[..2].iter().map(|s| s.chars().count() + 1).sum::<usize>();
+            Word::new(start, start + syllables[2].chars().count())
+        };
+
+        let mut analyzer = Analyzer::new(&a, &b);
+        analyzer.set_tokenizer(Box::new(Syllables));
+        let response = analyzer.run(&mut Monitor::default()).unwrap();
+        // One syllable of ten differs, however many chars it has
+        assert_eq!(response.overall_levenstein_similarity, 0.9);
+        let matched = response.result.iter().find(|x| x.levenshtein_match).unwrap();
+        let ends = (matched.a.start, matched.a.end, matched.b.start, matched.b.end);
+        assert_eq!(ends, (0, a.chars().count(), 0, b.chars().count()));
+
+        let mut corpus = Corpus::new(&[&a, &b]);
+        corpus.set_tokenizer(Box::new(Syllables));
+        let pairs = corpus.run(&mut Monitor::default()).unwrap().pairs;
+        let json = |response: &ResponseAndOverall| serde_json::to_string(response).unwrap();
+        assert_eq!(json(&pairs[0].response), json(&response));
+
+        // Linked, the differing syllables are the same token
+        analyzer.set_synonyms(vec![Synonym::new(third(&syllables_a), vec![third(&syllables_b)])], Vec::new());
+        let linked = analyzer.run(&mut Monitor::default()).unwrap();
+        assert_eq!(linked.overall_levenstein_similarity, 1.0);
+    }
+
+    #[test]
+    fn word_matches_report_char_offsets() {
+        let a = "In the beginning was the Word, and the Word was with God, and the Word was God.";
+        let b = "Ἐν ἀρχῇ — in the beginning was the Word, and the Word was with God!";
+        let config = AnalysisConfig {
+            tokenization: Tokenization::Word,
+            min_length: 3,
+            kernel_size: 3,
+            ..AnalysisConfig::default()
+        };
+        let response = crate::analyze(a, b, Vec::new(), Vec::new(), &config).unwrap();
+        let matched = response.result.iter().find(|x| x.levenshtein_match).unwrap();
+        let chars = |text: &str, start: usize, end: usize| text.chars().skip(start).take(end - start).collect::<String>();
+        // The match grows over In and in as a substitution, and the offsets count chars, not bytes
+        assert_eq!(chars(a, matched.a.start, matched.a.end), "In the beginning was the Word, and the Word was with God");
+        assert_eq!(chars(b, matched.b.start, matched.b.end), "in the beginning was the Word, and the Word was with God");
+    }
+
+    #[test]
+    fn grapheme_tokens_keep_clusters_whole() {
+        let text: Vec<char> = "e\u{301}葛\u{E0100}👨\u{200D}👩\u{200D}👧a".chars().collect();
+        let tokens = GraphemeTokenizer.tokenize(&text);
+        let spans: Vec<(usize, usize)> = tokens.iter().map(|span| (span.start, span.end)).collect();
+        assert_eq!(spans, [(0, 2), (2, 4), (4, 9), (9, 10)]);
+    }
+
+    #[test]
+    fn matches_never_split_a_cluster() {
+        // Both matches end on a cluster, the first on a mark and the second on a selector
+        let a = "天地玄黃宇宙洪荒e\u{301}ABCDEFGHIJKLMNOPQRSTUVWXYZ寒來暑往秋收冬藏葛\u{E0100}👨\u{200D}👩\u{200D}👧";
+        let b = "天地玄黃宇宙洪荒eabcdefghijklmnopqrstuvwxyz寒來暑往秋收冬藏葛";
+        let normalizations = [
+            Normalization::default(),
+            Normalization {
+                unicode_form: Some(UnicodeForm::Nfc),
+                ..Normalization::default()
+            },
+            // Leaves the marks and selectors out of the clusters they belong to
+            Normalization {
+                drop: vec![CharClass::Chars("\u{301}\u{E0100}\u{200D}".to_string())],
+                ..Normalization::default()
+            },
+        ];
+        for normalization in normalizations {
+            let config = AnalysisConfig {
+                tokenization: Tokenization::Grapheme,
+                normalization: normalization.clone(),
+                ..AnalysisConfig::default()
+            };
+            let response = crate::analyze(a, b, Vec::new(), Vec::new(), &config).unwrap();
+            assert!(response.result.iter().any(|x| x.levenshtein_match), "{:?}", normalization);
+            let boundaries_a = grapheme_boundaries(&a.chars().collect::<Vec<_>>());
+            let boundaries_b = grapheme_boundaries(&b.chars().collect::<Vec<_>>());
+            for result in &response.result {
+                for (substring, boundaries) in [(result.a, &boundaries_a), (result.b, &boundaries_b)] {
+                    assert!(boundaries[substring.start] && boundaries[substring.end], "{:?} {:?}", normalization, result);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,668 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::max,
+    ops::{Index, IndexMut},
+};
+
+use crate::{SelfComparison, costs::EditCosts, progress::CancellationToken, synonyms::Token};
+
+#[derive(Clone, Debug)]
+pub struct SubstringResult {
+    pub start_a: usize,
+    pub end_a: usize,
+    pub start_b: usize,
+    pub end_b: usize,
+    pub len: usize,
+    pub edit_ratio: f32,
+}
+
+// What an algorithm found, and how many candidates it skipped after hitting max_substrings
+#[derive(Debug, Default)]
+pub struct Matches {
+    pub results: Vec<SubstringResult>,
+    pub dropped: usize,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Substring {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Result {
+    pub a: Substring,
+    pub b: Substring,
+    pub similarity: f32,
+    #[serde(rename = "levenshteinMatch")]
+    pub levenshtein_match: bool,
+    // Whether homophones raised the similarity of the match
+    #[serde(default)]
+    pub phonetic: bool,
+}
+
+// Efficient matrix implementation - only stores last 2 rows to save memory
+pub struct EfficientMatrix<T> {
+    row_len: usize,
+    data: Vec<T>,
+}
+
+impl<T: std::clone::Clone> EfficientMatrix<T> {
+    pub fn new(inital: T, row_len: usize) -> Self {
+        EfficientMatrix {
+            row_len,
+            data: vec![inital; 2 * row_len],
+        }
+    }
+}
+
+impl<T> Index<usize> for EfficientMatrix<T> {
+    type Output = [T];
+
+    fn index(&self, index: usize) -> &[T] {
+        let base = (index % 2) * self.row_len;
+        &self.data[base..][..self.row_len]
+    }
+}
+
+impl<T> IndexMut<usize> for EfficientMatrix<T> {
+    fn index_mut(&mut self, index: usize) -> &mut [T] {
+        let base = (index % 2) * self.row_len;
+        &mut self.data[base..][..self.row_len]
+    }
+}
+
+pub fn levenshtein_edit_distance(a_chars: &[Token], b_chars: &[Token], costs: &EditCosts) -> f32 {
+    EditDistance::between(a_chars.iter(), b_chars.iter(), costs).distance()
+}
+
+pub fn cosine_similarity(str_a: &[char], str_b: &[char]) -> f32 {
+    // Find the frequency of each unicode character in the string
+    let mut a: FxHashMap<char, u64> = FxHashMap::default();
+    let mut b: FxHashMap<char, u64> = FxHashMap::default();
+    for c in str_a {
+        *a.entry(*c).or_insert(0) += 1;
+    }
+    for c in str_b {
+        *b.entry(*c).or_insert(0) += 1;
+    }
+    let mut dot_product = 0;
+    let mut norm_a = 0;
+    let mut norm_b = 0;
+    let mut all_keys = FxHashSet::default();
+    all_keys.extend(a.keys());
+    all_keys.extend(b.keys());
+    for i in all_keys {
+        let a_freq = *a.get(i).unwrap_or(&0);
+        let b_freq = *b.get(i).unwrap_or(&0);
+        dot_product += a_freq * b_freq;
+        norm_a += a_freq * a_freq;
+        norm_b += b_freq * b_freq;
+    }
+    // The product of the norms of long texts is beyond any integer type
+    ((dot_product as f64) / ((norm_a as f64) * (norm_b as f64)).sqrt()) as f32
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn recompute_ratio(
+    a: &[Token],
+    b: &[Token],
+    costs: &EditCosts,
+    start_a: usize,
+    new_end_a: usize,
+    start_b: usize,
+    new_end_b: usize,
+    new_len: usize,
+) -> f32 {
+    let edit_distance = levenshtein_edit_distance(&a[start_a..new_end_a], &b[start_b..new_end_b], costs);
+    edit_ratio(new_len, edit_distance)
+}
+
+// The share of the length that survives the edits
+pub fn edit_ratio(len: usize, edit_distance: f32) -> f32 {
+    (len as f32 - edit_distance) / len as f32
+}
+
+pub fn find_length_from_tokens(tokens_a: &[Token], tokens_b: &[Token]) -> usize {
+    let mut len = 0;
+    let max_len = max(tokens_a.len(), tokens_b.len());
+    for i in 0..max_len {
+        let token_a = tokens_a.get(i);
+        let token_b = tokens_b.get(i);
+        match (token_a, token_b) {
+            (Some(a), Some(b)) => len += max(a.len(), b.len()),
+            (Some(a), None) => len += a.len(),
+            (None, Some(b)) => len += b.len(),
+            (None, None) => {}
+        }
+    }
+    len
+}
+
+// The cells of a row or column of the DP matrix that lie in the band, from `start` on.
+// The cells outside of it are taken to cost more than any alignment the band is kept for.
+#[derive(Default)]
+struct Cells {
+    start: usize,
+    values: Vec<f32>,
+}
+
+impl Cells {
+    fn get(&self, index: usize) -> f32 {
+        let offset = index.checked_sub(self.start);
+        offset.and_then(|offset| self.values.get(offset)).copied().unwrap_or(f32::INFINITY)
+    }
+}
+
+// Edit distance between two sequences that only ever grow at the end.
+// Only the last two rows and columns of the DP matrix are kept, the ones before
+// the last for transpositions, and of those only the cells at most `band` steps
+// off the diagonal. Appending a token costs time linear in the band, and the
+// distance is exact as long as an optimal alignment stays within it.
+pub struct EditDistance<'c> {
+    costs: &'c EditCosts,
+    band: usize,
+    len_a: usize,
+    len_b: usize,
+    row: Cells,
+    column: Cells,
+    previous_row: Cells,
+    previous_column: Cells,
+    last_a: Option<Token>,
+    last_b: Option<Token>,
+}
+
+impl<'c> EditDistance<'c> {
+    pub fn new(costs: &'c EditCosts) -> Self {
+        EditDistance::with_band(costs, usize::MAX)
+    }
+
+    pub fn with_band(costs: &'c EditCosts, band: usize) -> Self {
+        EditDistance {
+            costs,
+            band,
+            len_a: 0,
+            len_b: 0,
+            row: Cells {
+                start: 0,
+                values: vec![0.0],
+            },
+            column: Cells {
+                start: 0,
+                values: vec![0.0],
+            },
+            previous_row: Cells::default(),
+            previous_column: Cells::default(),
+            last_a: None,
+            last_b: None,
+        }
+    }
+
+    pub fn between<'t, I>(a: I, b: I, costs: &'c EditCosts) -> Self
+    where
+        I: Iterator<Item = &'t Token> + Clone,
+    {
+        EditDistance::between_in_band(a, b, costs, usize::MAX)
+    }
+
+    pub fn between_in_band<'t, I>(a: I, b: I, costs: &'c EditCosts, band: usize) -> Self
+    where
+        I: Iterator<Item = &'t Token> + Clone,
+    {
+        let mut distance = EditDistance::with_band(costs, band);
+        for token in b.clone() {
+            distance.push_b(token, std::iter::empty());
+        }
+        for token in a {
+            distance.push_a(token, b.clone());
+        }
+        distance
+    }
+
+    pub fn band(&self) -> usize {
+        self.band
+    }
+
+    // The offsets of the cells of row or column `index` that lie in the band, up to `len`
+    fn window(&self, index: usize, len: usize) -> (usize, usize) {
+        (index.saturating_sub(self.band), len.min(index.saturating_add(self.band)))
+    }
+
+    // The cost of a cell from its neighbours, `swapped` being the cell two steps back on
+    // the diagonal if the last two tokens of each side are the same ones in swapped order
+    fn cell(&self, diagonal: f32, substitution: f32, deleted: f32, inserted: f32, swapped: Option<f32>) -> f32 {
+        let mut cost = (diagonal + substitution)
+            .min(deleted + self.costs.deletion)
+            .min(inserted + self.costs.insertion);
+        if let (Some(swapped), Some(transposition)) = (swapped, self.costs.transposition) {
+            cost = cost.min(swapped + transposition);
+        }
+        cost
+    }
+
+    // Appends a token to A, `b` must yield every token of B in order
+    pub fn push_a<'t>(&mut self, token: &Token, b: impl Iterator<Item = &'t Token>) {
+        let i = self.len_a + 1;
+        let (start, end) = self.window(i, self.len_b);
+        let mut new_row = Cells {
+            start,
+            values: Vec::with_capacity((end + 1).saturating_sub(start)),
+        };
+        // Only the tokens of B from two before the band on are needed
+        let mut b = b.skip(start.saturating_sub(2));
+        let (mut before, mut other): (Option<&Token>, Option<&Token>) = (None, None);
+        for _ in start.saturating_sub(2)..start {
+            (before, other) = (other, b.next());
+        }
+        for j in start..=end {
+            let cost = match other {
+                None => self.row.get(0) + self.costs.deletion,
+                Some(other) => {
+                    let swapped = match (self.last_a, before) {
+                        (Some(last), Some(before)) if last == *other && before == token => {
+                            Some(self.previous_row.get(j - 2))
+                        }
+                        _ => None,
+                    };
+                    let substitution = self.costs.substitution(token, other);
+                    self.cell(self.row.get(j - 1), substitution, self.row.get(j), new_row.get(j - 1), swapped)
+                }
+            };
+            new_row.values.push(cost);
+            (before, other) = (other, b.next());
+        }
+        // The last columns only hold the cells of this row that are in the band
+        if (start..=end).contains(&self.len_b) {
+            self.column.values.push(new_row.get(self.len_b));
+        }
+        if let Some(j) = self.len_b.checked_sub(1).filter(|j| (start..=end).contains(j)) {
+            self.previous_column.values.push(new_row.get(j));
+        }
+        self.previous_row = std::mem::replace(&mut self.row, new_row);
+        self.len_a = i;
+        self.last_a = Some(*token);
+    }
+
+    // Appends a token to B, `a` must yield every token of A in order
+    pub fn push_b<'t>(&mut self, token: &Token, a: impl Iterator<Item = &'t Token>) {
+        let j = self.len_b + 1;
+        let (start, end) = self.window(j, self.len_a);
+        let mut new_column = Cells {
+            start,
+            values: Vec::with_capacity((end + 1).saturating_sub(start)),
+        };
+        let mut a = a.skip(start.saturating_sub(2));
+        let (mut before, mut other): (Option<&Token>, Option<&Token>) = (None, None);
+        for _ in start.saturating_sub(2)..start {
+            (before, other) = (other, a.next());
+        }
+        for i in start..=end {
+            let cost = match other {
+                None => self.column.get(0) + self.costs.insertion,
+                Some(other) => {
+                    let swapped = match (self.last_b, before) {
+                        (Some(last), Some(before)) if last == *other && before == token => {
+                            Some(self.previous_column.get(i - 2))
+                        }
+                        _ => None,
+                    };
+                    let substitution = self.costs.substitution(other, token);
+                    self.cell(self.column.get(i - 1), substitution, new_column.get(i - 1), self.column.get(i), swapped)
+                }
+            };
+            new_column.values.push(cost);
+            (before, other) = (other, a.next());
+        }
+        if (start..=end).contains(&self.len_a) {
+            self.row.values.push(new_column.get(self.len_a));
+        }
+        if let Some(i) = self.len_a.checked_sub(1).filter(|i| (start..=end).contains(i)) {
+            self.previous_row.values.push(new_column.get(i));
+        }
+        self.previous_column = std::mem::replace(&mut self.column, new_column);
+        self.len_b = j;
+        self.last_b = Some(*token);
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.row.get(self.len_b)
+    }
+}
+
+// The band that keeps the edit distance of a match exact while its ratio holds. Straying
+// from the diagonal takes a deletion and an insertion per step beyond the difference in
+// length, and the ratio only allows so many edits. Growing the match allows more of them.
+fn edit_band(costs: &EditCosts, ratio: f32, len: usize, len_a: usize, len_b: usize) -> usize {
+    let detour = costs.insertion + costs.deletion;
+    if detour <= 0.0 {
+        return usize::MAX;
+    }
+    let edits = (1.0 - ratio).max(0.0) * len as f32;
+    len_a.abs_diff(len_b).saturating_add((edits / detour) as usize).saturating_add(1)
+}
+
+// Helper function to expand matches forward (right)
+#[allow(clippy::too_many_arguments)]
+pub fn expand_matches_forward(
+    a: &[Token],
+    b: &[Token],
+    costs: &EditCosts,
+    ratio: f32,
+    max_strike: usize,
+    self_comparison: SelfComparison,
+    ret: &mut SubstringResult,
+    cancellation: &CancellationToken,
+) {
+    let mut new_end_a = ret.end_a;
+    let mut new_end_b = ret.end_b;
+    let mut new_len = ret.len;
+    let start_a = ret.start_a;
+    let start_b = ret.start_b;
+    let mut strike = 0;
+    // The band is kept half as wide again as it has to be, so that it is seldom filled again
+    let band = edit_band(costs, ratio, new_len, new_end_a - start_a, new_end_b - start_b);
+    let (seed_a, seed_b) = (a[start_a..new_end_a].iter(), b[start_b..new_end_b].iter());
+    let mut distance = EditDistance::between_in_band(seed_a, seed_b, costs, band.saturating_add(band / 2));
+
+    while strike < max_strike
+        && new_end_a < a.len()
+        && new_end_b < b.len()
+        && self_comparison.allows(start_a, new_end_a + 1, start_b)
+        && !cancellation.is_cancelled()
+    {
+        // Expand
+        new_end_a += 1;
+        new_end_b += 1;
+        new_len += max(a[new_end_a - 1].len(), b[new_end_b - 1].len());
+        let band = edit_band(costs, ratio, new_len, new_end_a - start_a, new_end_b - start_b);
+        if band > distance.band() {
+            // The longer match allows more edits than the band holds
+            let (a, b) = (a[start_a..new_end_a].iter(), b[start_b..new_end_b].iter());
+            distance = EditDistance::between_in_band(a, b, costs, band.saturating_add(band / 2));
+        } else {
+            distance.push_a(&a[new_end_a - 1], b[start_b..new_end_b - 1].iter());
+            distance.push_b(&b[new_end_b - 1], a[start_a..new_end_a].iter());
+        }
+
+        let new_ratio = edit_ratio(new_len, distance.distance());
+
+        if new_ratio < ratio {
+            strike += 1;
+        } else {
+            strike = 0;
+            ret.end_a = new_end_a;
+            ret.end_b = new_end_b;
+            ret.len = new_len;
+            ret.edit_ratio = new_ratio;
+        }
+    }
+}
+
+// Helper function to expand matches backward (left)
+// The sequences are read back to front so that they still grow at the end
+#[allow(clippy::too_many_arguments)]
+pub fn expand_matches_backward(
+    a: &[Token],
+    b: &[Token],
+    costs: &EditCosts,
+    ratio: f32,
+    max_strike: usize,
+    self_comparison: SelfComparison,
+    ret: &mut SubstringResult,
+    cancellation: &CancellationToken,
+) {
+    let mut new_start_a: usize = ret.start_a;
+    let mut new_start_b: usize = ret.start_b;
+    let mut new_len: usize = ret.len;
+    let end_a = ret.end_a;
+    let end_b = ret.end_b;
+    let mut strike = 0;
+    let band = edit_band(costs, ratio, new_len, end_a - new_start_a, end_b - new_start_b);
+    let (seed_a, seed_b) = (a[new_start_a..end_a].iter().rev(), b[new_start_b..end_b].iter().rev());
+    let mut distance = EditDistance::between_in_band(seed_a, seed_b, costs, band.saturating_add(band / 2));
+
+    while strike < max_strike
+        && new_start_a > 0
+        && new_start_b > 0
+        && self_comparison.allows(new_start_a - 1, end_a, new_start_b - 1)
+        && !cancellation.is_cancelled()
+    {
+        // Expand
+        new_start_a -= 1;
+        new_start_b -= 1;
+        new_len += max(a[new_start_a].len(), b[new_start_b].len());
+        let band = edit_band(costs, ratio, new_len, end_a - new_start_a, end_b - new_start_b);
+        if band > distance.band() {
+            let (a, b) = (a[new_start_a..end_a].iter().rev(), b[new_start_b..end_b].iter().rev());
+            distance = EditDistance::between_in_band(a, b, costs, band.saturating_add(band / 2));
+        } else {
+            distance.push_a(&a[new_start_a], b[new_start_b + 1..end_b].iter().rev());
+            distance.push_b(&b[new_start_b], a[new_start_a..end_a].iter().rev());
+        }
+
+        let new_ratio = edit_ratio(new_len, distance.distance());
+
+        if new_ratio < ratio {
+            strike += 1;
+        } else {
+            strike = 0;
+            ret.start_a = new_start_a;
+            ret.start_b = new_start_b;
+            ret.len = new_len;
+            ret.edit_ratio = new_ratio;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn expand_match_left_and_right(
+    substr: &mut SubstringResult,
+    a: &[Token],
+    b: &[Token],
+    costs: &EditCosts,
+    ratio: f32,
+    max_strike: usize,
+    self_comparison: SelfComparison,
+    cancellation: &CancellationToken,
+) {
+    // Expand to the right
+    expand_matches_forward(a, b, costs, ratio, max_strike, self_comparison, substr, cancellation);
+
+    // Expand to the left
+    expand_matches_backward(a, b, costs, ratio, max_strike, self_comparison, substr, cancellation);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        costs::CostModel,
+        test_utils::{Random, tokens},
+    };
+
+    // The full optimal string alignment matrix, filled the textbook way
+    fn osa(a: &[Token], b: &[Token], costs: &EditCosts) -> f32 {
+        let mut d = vec![vec![0.0; b.len() + 1]; a.len() + 1];
+        for i in 0..=a.len() {
+            for j in 0..=b.len() {
+                d[i][j] = match (i, j) {
+                    (0, _) => j as f32 * costs.insertion,
+                    (_, 0) => i as f32 * costs.deletion,
+                    _ => (d[i - 1][j - 1] + costs.substitution(&a[i - 1], &b[j - 1]))
+                        .min(d[i - 1][j] + costs.deletion)
+                        .min(d[i][j - 1] + costs.insertion),
+                };
+                if let Some(transposition) = costs.transposition
+                    && i > 1
+                    && j > 1
+                    && a[i - 1] == b[j - 2]
+                    && a[i - 2] == b[j - 1]
+                {
+                    d[i][j] = d[i][j].min(d[i - 2][j - 2] + transposition);
+                }
+            }
+        }
+        d[a.len()][b.len()]
+    }
+
+    #[test]
+    fn edit_distance_matches_full_matrix() {
+        let model = CostModel {
+            insertion: 0.7,
+            deletion: 1.3,
+            substitution: 1.1,
+            transposition: Some(0.5),
+            substitutions: Vec::new(),
+        };
+        // A cheaper pair of keys, like a substitution group
+        let costs = EditCosts::new(&model, [(0, 1, 0.4)]);
+        let mut random = Random::new(0x2545f4914f6cdd1d);
+        for _ in 0..300 {
+            let keys_a = random.keys(8, 4);
+            let keys_b = random.keys(8, 4);
+            let (a, b) = (tokens(&keys_a), tokens(&keys_b));
+            let expected = osa(&a, &b, &costs);
+            let between = EditDistance::between(a.iter(), b.iter(), &costs).distance();
+            assert!((between - expected).abs() < 1e-4, "{:?} {:?}: {} != {}", keys_a, keys_b, between, expected);
+
+            // Growing both sides in a random order goes through every prefix pair on the way
+            let mut distance = EditDistance::new(&costs);
+            let (mut i, mut j) = (0, 0);
+            while i < a.len() || j < b.len() {
+                if j == b.len() || (i < a.len() && random.below(2) == 0) {
+                    distance.push_a(&a[i], b[..j].iter());
+                    i += 1;
+                } else {
+                    distance.push_b(&b[j], a[..i].iter());
+                    j += 1;
+                }
+                let expected = osa(&a[..i], &b[..j], &costs);
+                assert!(
+                    (distance.distance() - expected).abs() < 1e-4,
+                    "{:?} {:?}: {} != {}",
+                    &keys_a[..i],
+                    &keys_b[..j],
+                    distance.distance(),
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn banded_edit_distance_is_exact_within_its_band() {
+        let model = CostModel {
+            insertion: 0.8,
+            deletion: 1.2,
+            substitution: 1.5,
+            transposition: Some(0.5),
+            substitutions: Vec::new(),
+        };
+        let costs = EditCosts::new(&model, []);
+        let mut random = Random::new(4);
+        for _ in 0..500 {
+            let keys_a = random.keys(12, 3);
+            let keys_b = random.keys(12, 3);
+            let (a, b) = (tokens(&keys_a), tokens(&keys_b));
+            let band = random.below(6) as usize;
+            let expected = osa(&a, &b, &costs);
+            let banded = EditDistance::between_in_band(a.iter(), b.iter(), &costs, band).distance();
+            // Leaving the band takes a detour of a deletion and an insertion per step
+            let detour = band as f32 + 1.0 - a.len().abs_diff(b.len()) as f32;
+            if expected < detour * (costs.insertion + costs.deletion) {
+                let (a, b) = (&keys_a, &keys_b);
+                assert!((banded - expected).abs() < 1e-4, "{:?} {:?} {}: {} != {}", a, b, band, banded, expected);
+            } else {
+                assert!(banded >= expected - 1e-4, "{:?} {:?} {}: {} < {}", keys_a, keys_b, band, banded, expected);
+            }
+        }
+    }
+
+    fn expand(a: &[Token], b: &[Token], costs: &EditCosts, seed: &SubstringResult, ratio: f32) -> SubstringResult {
+        let mut substr = seed.clone();
+        let cancellation = CancellationToken::new();
+        expand_match_left_and_right(&mut substr, a, b, costs, ratio, 2, SelfComparison::Off, &cancellation);
+        substr
+    }
+
+    #[test]
+    fn expansion_follows_the_cost_model() {
+        // Equal but for 4 and 5 swapped
+        let a = tokens(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let b = tokens(&[0, 1, 2, 3, 5, 4, 6, 7, 8, 9]);
+        let seed = SubstringResult {
+            start_a: 0,
+            end_a: 4,
+            start_b: 0,
+            end_b: 4,
+            len: 4,
+            edit_ratio: 1.0,
+        };
+        let unit = expand(&a, &b, &EditCosts::default(), &seed, 0.9);
+        assert_eq!((unit.end_a, unit.edit_ratio), (4, 1.0));
+        let model = CostModel {
+            transposition: Some(0.2),
+            ..CostModel::default()
+        };
+        let swapped = expand(&a, &b, &EditCosts::new(&model, []), &seed, 0.9);
+        assert_eq!((swapped.end_a, swapped.end_b, swapped.len), (10, 10, 10));
+        assert!((swapped.edit_ratio - 0.98).abs() < 1e-6);
+    }
+
+    #[test]
+    fn expanded_ratios_equal_recomputed_ones() {
+        let model = CostModel {
+            insertion: 0.6,
+            deletion: 1.4,
+            substitution: 0.9,
+            transposition: Some(0.3),
+            substitutions: Vec::new(),
+        };
+        let costs = EditCosts::new(&model, [(0, 1, 0.2)]);
+        let mut random = Random::new(22);
+        for _ in 0..300 {
+            let a = tokens(&random.keys(30, 4));
+            let b = tokens(&random.keys(30, 4));
+            // Any pair of equal tokens seeds the expansion
+            let pairs = (0..a.len()).flat_map(|i| (0..b.len()).map(move |j| (i, j)));
+            let Some((i, j)) = pairs.filter(|&(i, j)| a[i] == b[j]).nth(random.below(4) as usize) else {
+                continue;
+            };
+            let seed = SubstringResult {
+                start_a: i,
+                end_a: i + 1,
+                start_b: j,
+                end_b: j + 1,
+                len: 1,
+                edit_ratio: 1.0,
+            };
+            let x = expand(&a, &b, &costs, &seed, 0.7);
+            let len = find_length_from_tokens(&a[x.start_a..x.end_a], &b[x.start_b..x.end_b]);
+            assert_eq!(x.len, len);
+            let recomputed = recompute_ratio(&a, &b, &costs, x.start_a, x.end_a, x.start_b, x.end_b, len);
+            let span = (x.start_a, x.end_a, x.start_b, x.end_b);
+            assert!((x.edit_ratio - recomputed).abs() < 1e-4, "{:?}: {} != {}", span, x.edit_ratio, recomputed);
+            assert!(x.edit_ratio >= 0.7);
+        }
+    }
+
+    #[test]
+    fn long_seeds_only_fill_the_band() {
+        // A seed of a long passage, which grows by the one token left of it
+        let a = tokens(&(0..20_000).map(|i| i % 1000).collect::<Vec<_>>());
+        let seed = SubstringResult {
+            start_a: 0,
+            end_a: 19_999,
+            start_b: 0,
+            end_b: 19_999,
+            len: 19_999,
+            edit_ratio: 1.0,
+        };
+        let started = std::time::Instant::now();
+        let x = expand(&a, &a, &EditCosts::default(), &seed, 0.99);
+        // Filling the whole matrix of the seed took minutes
+        assert!(started.elapsed().as_secs() < 30, "{:?}", started.elapsed());
+        assert_eq!((x.end_a, x.end_b, x.len, x.edit_ratio), (20_000, 20_000, 20_000, 1.0));
+    }
+}
@@ -0,0 +1,103 @@
+use rustc_hash::FxHashMap;
+use std::collections::hash_map::Entry;
+
+// Reads a table with one group of variant characters per line, such as traditional and
+// simplified forms separated by tabs. Lines starting with '#' are comments.
+pub fn parse_variant_groups(table: &str) -> Vec<String> {
+    table
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(|line| line.chars().filter(|c| !c.is_whitespace()).collect::<String>())
+        .filter(|group| group.chars().nth(1).is_some())
+        .collect()
+}
+
+// Maps every variant to the first character of its group. Groups sharing a character
+// are merged, so the tables may be split over several files.
+#[derive(Debug, Default)]
+pub struct VariantTable {
+    canonical: FxHashMap<char, char>,
+}
+
+impl VariantTable {
+    pub fn new(groups: &[String]) -> Self {
+        let mut table = VariantTable::default();
+        for group in groups {
+            table.add_group(group);
+        }
+        table
+    }
+
+    fn add_group(&mut self, group: &str) {
+        let Some(first) = group.chars().next() else {
+            return;
+        };
+        let canonical = self.canonical(first);
+        // Canonical characters map to themselves, so that every grouped character is a key
+        self.canonical.insert(canonical, canonical);
+        for c in group.chars() {
+            let old = self.canonical(c);
+            if old == canonical {
+                continue;
+            }
+            match self.canonical.entry(c) {
+                Entry::Occupied(_) => {
+                    // c already belongs to another group, which is merged into this one
+                    for value in self.canonical.values_mut().filter(|value| **value == old) {
+                        *value = canonical;
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(canonical);
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.canonical.is_empty()
+    }
+
+    pub fn canonical(&self, c: char) -> char {
+        self.canonical.get(&c).copied().unwrap_or(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Random;
+
+    #[test]
+    fn parses_groups_skipping_comments_and_single_characters() {
+        let table = "# traditional\tsimplified\n說\t说\n爲 為\t为\n\n丁\n";
+        assert_eq!(parse_variant_groups(table), ["說说", "爲為为"]);
+    }
+
+    #[test]
+    fn groups_sharing_a_character_are_merged() {
+        let mut random = Random::new(15);
+        for _ in 0..200 {
+            let groups: Vec<String> = (0..random.below(6))
+                .map(|_| (0..random.below(4)).map(|_| char::from(b'a' + random.below(12) as u8)).collect())
+                .collect();
+            let table = VariantTable::new(&groups);
+            // Two characters are variants of each other if a chain of groups links them
+            let mut component: Vec<usize> = (0..12).collect();
+            for group in &groups {
+                let members: Vec<usize> = group.bytes().map(|c| (c - b'a') as usize).collect();
+                for &c in members.iter().skip(1) {
+                    let (from, to) = (component[c], component[members[0]]);
+                    component.iter_mut().filter(|x| **x == from).for_each(|x| *x = to);
+                }
+            }
+            for x in 0..12u8 {
+                for y in 0..12u8 {
+                    let (cx, cy) = (char::from(b'a' + x), char::from(b'a' + y));
+                    let same = component[x as usize] == component[y as usize];
+                    assert_eq!(table.canonical(cx) == table.canonical(cy), same, "{:?} {} {}", groups, cx, cy);
+                }
+            }
+        }
+    }
+}
@@ -89,6 +89,9 @@ export default function ConfigurationForm({ children, onSubmit, disabled }:
             variant='standard'
           >
             <MenuItem value={Algorithm.Comparativus}>Comparativus</MenuItem>
+            <MenuItem value={Algorithm.SmithWaterman}>Smith-Waterman</MenuItem>
+            <MenuItem value={Algorithm.GreedyStringTiling}>Greedy String Tiling</MenuItem>
+            <MenuItem value={Algorithm.SuffixArray}>Suffix Array</MenuItem>
           </TextField>
         </Grid>
       </Grid>
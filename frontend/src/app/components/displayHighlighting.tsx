@@ -1,5 +1,5 @@
 import { Box, Grid2 as Grid, Typography } from "@mui/material"
-import type { Pair } from "../types"
+import type { Pair, SynonymClass } from "../types"
 import { useCallback, useEffect, useMemo } from "react"
 import React from "react"
 import { useProject } from "../utils/useProject"
@@ -113,28 +113,34 @@ export function DisplayHighlighting(props: { onContextMenu?: (charIndex: number,
     [result.pairs],
   )
 
-  const getContainingSynonym = useCallback(
+  // Older projects only have the synonyms as entered, which link a word to the words it was given
+  const synonymClasses = useMemo<SynonymClass[]>(
+    () => result.synonymClasses ?? result.synonymsA.map((syn) => ({ a: [syn.word], b: syn.synonyms })),
+    [result.synonymClasses, result.synonymsA],
+  )
+
+  const getContainingSynonymClasses = useCallback(
     (index: number, text: "a" | "b") => {
-      const synonyms = text === "a" ? result.synonymsA : result.synonymsB
-      const synonymResults = synonyms.filter(
-        (syn) => syn.word.start <= index && index < syn.word.end,
+      return synonymClasses.filter((synonymClass) =>
+        synonymClass[text].some((word) => word.start <= index && index < word.end),
       )
-      return synonymResults
     },
-    [result.synonymsA, result.synonymsB],
+    [synonymClasses],
   )
 
 
   const blinkSynonymsFromCharIndex = useCallback(
     (index: number, blink = true) => {
-      getContainingSynonym(index, "a").forEach((syn) => {
-        blinkRange(syn.word.start, syn.word.end, aRefs, blink)
-        syn.synonyms.forEach((word) => {
+      getContainingSynonymClasses(index, "a").forEach((synonymClass) => {
+        synonymClass.a.forEach((word) => {
+          blinkRange(word.start, word.end, aRefs, blink)
+        })
+        synonymClass.b.forEach((word) => {
           blinkRange(word.start, word.end, bRefs, blink)
         })
       })
     },
-    [aRefs, bRefs, blinkRange, getContainingSynonym],
+    [aRefs, bRefs, blinkRange, getContainingSynonymClasses],
   )
 
   const highlightFromPair = useCallback(
@@ -142,9 +148,10 @@ export function DisplayHighlighting(props: { onContextMenu?: (charIndex: number,
       if (pair.levenshteinMatch) color = matchColor
       if (!color) color = COLOR_LIST[index % COLOR_LIST.length]
       const similarityType = pair.levenshteinMatch ? "Edit Ratio" : "Cosine"
+      const phonetic = pair.phonetic ? " (homophones)" : ""
       const title = `${similarityType} similarity: ${pair.similarity.toFixed(
         4,
-      )}`
+      )}${phonetic}`
       highlightRange(pair.b.start, pair.b.end, color, bRefs, title)
       highlightRange(pair.a.start, pair.a.end, color, aRefs, title)
     },
@@ -47,8 +47,8 @@ export default function Run() {
   const handleSubmit = React.useCallback(async (data: InputData) => {
     setStatusMessage("Processing... please wait")
     try {
-      const textA = SubstringAlgorithm.clean_text(await data.fileA.text())
-      const textB = SubstringAlgorithm.clean_text(await data.fileB.text())
+      const textA = await data.fileA.text()
+      const textB = await data.fileB.text()
 
       setIsReady(false)
       runAnalysisFromTextAndConfig(
@@ -6,12 +6,20 @@ export type Pair = Omit<Result, "free"> & {
   hold?: boolean;
 }
 
+// Words of both texts that all count as the same word, as resolved by the analysis
+export type SynonymClass = {
+  a: Substring[];
+  b: Substring[];
+}
+
 export type DisplayResultState = {
   textA: string;
   textB: string;
   pairs: Pair[];
   overallLevensteinSimilarity: number;
   overallCosineSimilarity: number;
+  // Missing in projects exported before the classes were resolved
+  synonymClasses?: SynonymClass[];
 } & ConfigurationOptions
 
 export type InputData = {
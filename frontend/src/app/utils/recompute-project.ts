@@ -10,22 +10,28 @@ export default function useComputeAnalysis(
 ) {
   const showNotification = useNotification()
   const history = React.useRef<ConfigurationOptions[]>([])
+  // Reusing the session for the same texts keeps their tokens and n-gram index when only the config changes
+  const analyzer = React.useRef<{ textA: string; textB: string; session: SubstringAlgorithm.Analyzer } | null>(null)
   const computeAnalysis = React.useCallback(
     (textA: string, textB: string, config: ConfigurationOptions, addToHistory = true) => {
       try {
-        const newResult = SubstringAlgorithm.process(
-          textA,
-          textB,
-          config.minLength,
-          config.ratio,
-          config.maxStrikes,
-          20000,
-          config.kernelSize,
-          config.baseMatchSize,
-          config.algorithmSelection,
-          config.synonymsA,
-          config.synonymsB,
-        )
+        if (!analyzer.current || analyzer.current.textA !== textA || analyzer.current.textB !== textB) {
+          analyzer.current?.session.free()
+          analyzer.current = { textA, textB, session: new SubstringAlgorithm.Analyzer(textA, textB) }
+        }
+        const session = analyzer.current.session
+        session.set_config({
+          version: 1,
+          algorithm: SubstringAlgorithm.Algorithm[config.algorithmSelection] as keyof typeof SubstringAlgorithm.Algorithm,
+          minLength: config.minLength,
+          ratio: config.ratio,
+          maxStrikes: config.maxStrikes,
+          kernelSize: config.kernelSize,
+          baseMatchSize: config.baseMatchSize,
+          normalization: { drop: ["classic"] },
+        } satisfies SubstringAlgorithm.AnalysisConfig)
+        session.set_synonyms(config.synonymsA, config.synonymsB)
+        const newResult = session.run()
          
         if (addToHistory && result) history.current.push(cloneDeep({
           minLength: result.minLength,
@@ -45,6 +51,7 @@ export default function useComputeAnalysis(
           pairs: newResult.result,
           overallLevensteinSimilarity: newResult.overall_levenstein_similarity,
           overallCosineSimilarity: newResult.overall_cosine_similarity,
+          synonymClasses: newResult.synonym_classes,
           minLength: config.minLength,
           maxStrikes: config.maxStrikes,
           ratio: config.ratio,
@@ -56,9 +63,17 @@ export default function useComputeAnalysis(
           fileNameA: config.fileNameA,
           fileNameB: config.fileNameB,
         })
+        for (const warning of newResult.warnings) {
+          if (warning.kind === "maxSubstringsReached") {
+            showNotification(
+              `Stopped after ${warning.limit} matches, ${warning.dropped} more candidates were not examined.`,
+              "warning",
+            )
+          }
+        }
       } catch (error) {
         console.error("Error during analysis:", error)
-        showNotification("An error occurred during analysis.", "error")
+        showNotification(error instanceof Error ? error.message : "An error occurred during analysis.", "error")
         return
       }
     },
@@ -26,6 +26,7 @@ export default function useExportResult() {
         fileNameB: result.fileNameB,
         overallCosineSimilarity: result.overallCosineSimilarity,
         overallLevensteinSimilarity: result.overallLevensteinSimilarity,
+        synonymClasses: result.synonymClasses,
       }
 
       const file = await exportToFile(jsResultCopy)
@@ -1,204 +1,312 @@
 extern crate wasm_bindgen;
 use gloo_utils::format::JsValueSerdeExt;
-use serde::Serialize;
-use std::{
-    cmp::min,
-    panic::{self, PanicHookInfo},
-};
+use std::panic::{self, PanicHookInfo};
+use substr_core::{AnalysisConfig, CancellationToken, Error, Monitor, synonyms};
 use wasm_bindgen::prelude::*;
 
-mod comparativus;
-mod synonyms;
 mod utils;
 
+// The types below mirror the ones in substr_core so that the frontend gets
+// TypeScript definitions for the values passed through serde
+
 #[derive(PartialEq)]
 #[wasm_bindgen]
 pub enum Algorithm {
     Comparativus,
+    SmithWaterman,
+    GreedyStringTiling,
+    SuffixArray,
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const ANALYSIS_CONFIG: &'static str = r#"
+export interface AnalysisConfig {
+  version?: number;
+  algorithm?: keyof typeof Algorithm;
+  tokenization?: "Character" | "Grapheme" | "Word";
+  selfComparison?: "Off" | "Overlapping" | "Disjoint";
+  minLength?: number;
+  ratio?: number;
+  maxStrikes?: number;
+  maxSubstrings?: number;
+  kernelSize?: number;
+  baseMatchSize?: number;
+  extendRepeats?: boolean;
+  scoring?: { matchScore?: number; mismatchScore?: number; gapScore?: number };
+  normalization?: Normalization;
+  originalOffsets?: boolean;
+  variants?: string[];
+  dictionary?: { forms: string[]; cost?: number }[];
+  costs?: CostModel;
+  phonetic?: { readings?: string[]; cost?: number };
+}
+export interface CostModel {
+  insertion?: number;
+  deletion?: number;
+  substitution?: number;
+  transposition?: number | null;
+  substitutions?: { chars: string; cost?: number }[];
+}
+export type CharClass = "classic" | "punctuation" | "symbol" | "whitespace" | "number" | { chars: string };
+export interface Normalization {
+  widthFold?: boolean;
+  unicodeForm?: "NFC" | "NFKC" | null;
+  caseFold?: boolean;
+  drop?: CharClass[];
+  collapseWhitespace?: boolean;
+}
+"#;
+
+// Returning false from the callback cancels the analysis. It is the only chance to do so
+// while process runs, so a Web Worker should check its cancel flag in there.
+#[wasm_bindgen(typescript_custom_section)]
+const PROGRESS: &'static str = r#"
+export interface Progress {
+  fraction: number;
+  matches: number;
+}
+export type ProgressCallback = (progress: Progress) => boolean | void;
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "ProgressCallback")]
+    pub type ProgressCallback;
+}
+
+// What the functions below throw when the analysis fails. The message is the one of
+// `substr_core::Error`, the kind and the other fields those of its variant.
+#[wasm_bindgen(typescript_custom_section)]
+const ANALYSIS_ERROR: &'static str = r#"
+export type AnalysisError = Error & (
+  | { kind: "invalidParameter"; name: string; reason: string }
+  | { kind: "malformedSynonym"; reason: string }
+  | { kind: "textTooShort"; text: string; length: number; required: number }
+  | { kind: "limitExceeded"; limit: string; value: number; maximum: number }
+  | { kind: "cancelled" }
+);
+"#;
+
+// A JS Error carrying the fields of a `substr_core::Error`, or the message of any other error
+pub struct Thrown(JsValue);
+
+impl From<Error> for Thrown {
+    fn from(error: Error) -> Self {
+        let thrown = js_sys::Error::new(&error.to_string());
+        if let Ok(fields) = JsValue::from_serde(&error) {
+            js_sys::Object::assign(&thrown, fields.unchecked_ref());
+        }
+        Thrown(thrown.into())
+    }
+}
+
+impl From<JsError> for Thrown {
+    fn from(error: JsError) -> Self {
+        Thrown(error.into())
+    }
+}
+
+impl From<Thrown> for JsValue {
+    fn from(thrown: Thrown) -> Self {
+        thrown.0
+    }
+}
+
+fn to_js(value: &impl serde::Serialize) -> std::result::Result<JsValue, Thrown> {
+    Ok(JsValue::from_serde(value).map_err(JsError::from)?)
 }
 
 #[wasm_bindgen]
-#[derive(Serialize)]
-pub struct ResponseAndOverall {
-    result: Vec<utils::Result>,
-    overall_levenstein_similarity: f32,
-    overall_cosine_similarity: f32,
+#[derive(Clone, Copy)]
+pub struct Substring {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+#[allow(non_snake_case)]
+pub struct Result {
+    pub a: Substring,
+    pub b: Substring,
+    pub similarity: f32,
+    pub levenshteinMatch: bool,
+    pub phonetic: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[wasm_bindgen]
+pub struct Word {
+    pub start: usize,
+    pub end: usize,
+    // Between 0 and 1, see `substr_core::synonyms::Word`
+    pub cost: Option<f32>,
+}
+
+#[wasm_bindgen]
+impl Word {
+    #[wasm_bindgen(constructor)]
+    pub fn new(start: usize, end: usize) -> Self {
+        Word { start, end, cost: None }
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::should_implement_trait)]
+    pub fn clone(&self) -> Self {
+        Word {
+            start: self.start,
+            end: self.end,
+            cost: self.cost,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct Synonym {
+    pub word: Word,
+    synonyms: Vec<Word>,
+}
+
+#[wasm_bindgen]
+impl Synonym {
+    #[wasm_bindgen(getter)]
+    pub fn synonyms(&self) -> Vec<Word> {
+        self.synonyms.clone()
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_synonyms(&mut self, synonyms: Vec<Word>) {
+        self.synonyms = synonyms;
+    }
+    #[wasm_bindgen(constructor)]
+    pub fn new(word: Word, synonyms: Vec<Word>) -> Self {
+        Synonym { word, synonyms }
+    }
 }
 
 #[wasm_bindgen]
 pub fn process(
     str_a: String,
     str_b: String,
-    min_length: usize,
-    ratio: f32,
-    max_strikes: usize,
-    max_substrings: usize,
-    kernel_size: usize,
-    base_match_size: usize,
-    levenshtein_algorithm: Algorithm,
+    config: JsValue,
     synonyms_a: JsValue,
     synonyms_b: JsValue,
-) -> JsValue {
-    let file_a: Vec<char> = str_a.chars().collect();
-    let file_b: Vec<char> = str_b.chars().collect();
-    let mut synonyms_a = synonyms_a.into_serde::<Vec<synonyms::Synonym>>().unwrap();
-    synonyms_a.sort_unstable_by_key(|s| s.word.start);
-    let mut synonyms_b = synonyms_b.into_serde::<Vec<synonyms::Synonym>>().unwrap();
-    synonyms_b.sort_unstable_by_key(|s| s.word.start);
-    let token_a = synonyms::tokenize_text(0, file_a.len(), &synonyms_a, file_a.as_slice());
-    let token_b = synonyms::tokenize_text(0, file_b.len(), &synonyms_b, file_b.as_slice());
-    let levenshtein_distances: Vec<utils::SubstringResult>;
-    match levenshtein_algorithm {
-        Algorithm::Comparativus => {
-            levenshtein_distances = comparativus::find_levenshtein_matches(
-                token_a.as_slice(),
-                token_b.as_slice(),
-                min_length,
-                ratio,
-                max_substrings,
-                max_strikes,
-                kernel_size,
-                base_match_size,
-            );
+    on_progress: Option<ProgressCallback>,
+) -> std::result::Result<JsValue, Thrown> {
+    let config = read_config(config)?;
+    let synonyms_a = read_synonyms(synonyms_a)?;
+    let synonyms_b = read_synonyms(synonyms_b)?;
+    let response =
+        substr_core::analyze_with_monitor(&str_a, &str_b, synonyms_a, synonyms_b, &config, &mut monitor(on_progress))?;
+    to_js(&response)
+}
+
+// Compares every text with every other one, tokenizing each of them only once
+#[wasm_bindgen]
+pub fn process_corpus(
+    texts: Vec<String>,
+    config: JsValue,
+    on_progress: Option<ProgressCallback>,
+) -> std::result::Result<JsValue, Thrown> {
+    let mut corpus = substr_core::Corpus::new(&texts);
+    corpus.set_config(read_config(config)?);
+    let response = corpus.run(&mut monitor(on_progress))?;
+    to_js(&response)
+}
+
+// Keeps both texts between runs, so that changing the synonyms or the config
+// does not tokenize and index the texts again unless it has to
+#[wasm_bindgen]
+pub struct Analyzer {
+    inner: substr_core::Analyzer,
+}
+
+#[wasm_bindgen]
+impl Analyzer {
+    #[wasm_bindgen(constructor)]
+    pub fn new(str_a: String, str_b: String) -> Self {
+        Analyzer {
+            inner: substr_core::Analyzer::new(&str_a, &str_b),
         }
     }
-    if levenshtein_distances.is_empty() {
-        return JsValue::from_serde(&ResponseAndOverall {
-            overall_levenstein_similarity: utils::recompute_ratio(&token_a, &token_b, 0, token_a.len(), 0, token_b.len(), utils::find_length_from_tokens(&token_a, &token_b)),
-            overall_cosine_similarity: utils::cosine_similarity(&file_a.as_slice(), &file_b.as_slice()),
-            result: vec![],
-        }).unwrap()
+    pub fn set_config(&mut self, config: JsValue) -> std::result::Result<(), Thrown> {
+        self.inner.set_config(read_config(config)?);
+        Ok(())
     }
-
-    let mut matches_a = levenshtein_distances[..].to_vec();
-    matches_a.sort_unstable_by_key(|x| x.start_a);
-    let mut matches_b = levenshtein_distances[..].to_vec();
-    matches_b.sort_unstable_by_key(|x| x.start_b);
-
-    let mut result: Vec<utils::Result> = Vec::with_capacity(levenshtein_distances.len() * 2 + 1);
-
-    let mut add_levenshtein_match = |elem: &utils::SubstringResult| {
-        let a = utils::Substring {
-            start: token_a[elem.start_a].start,
-            end: token_a[elem.end_a-1].end,
-        };
-        let b = utils::Substring {
-            start: token_b[elem.start_b].start,
-            end: token_b[elem.end_b-1].end,
-        };
-        let similarity = elem.edit_ratio;
-        result.push(utils::Result {
-            a,
-            b,
-            similarity,
-            levenshteinMatch: true,
-        });
-    };
-
-    matches_a.iter().for_each(|elem| {
-        add_levenshtein_match(&elem);
-    });
-
-    // Merge overlapping matches in both lists
-    matches_a.dedup_by(|a, b| {
-        if a.start_a <= b.end_a && b.start_a <= a.end_a {
-            a.start_a = a.start_a.min(b.start_a);
-            a.end_a = a.end_a.max(b.end_a);
-            b.start_a = a.start_a;
-            b.end_a = a.end_a;
-            true
-        } else {
-            false
-        }
-    });
-    matches_b.dedup_by(|a, b| {
-        if a.start_b <= b.end_b && b.start_b <= a.end_b {
-            a.start_b = a.start_b.min(b.start_b);
-            a.end_b = a.end_b.max(b.end_b);
-            b.start_b = a.start_b;
-            b.end_b = a.end_b;
-            true
-        } else {
-            false
-        }
-    });
-
-    let add_cosine_similarity_to_result = |cosine: &mut utils::Result| {
-        if cosine.a.start < cosine.a.end && cosine.b.start < cosine.b.end {
-            cosine.similarity = utils::cosine_similarity(
-                &file_a[cosine.a.start..cosine.a.end],
-                &file_b[cosine.b.start..cosine.b.end],
-            );
-        } else {
-            cosine.similarity = 0.0;
-        }
-        *cosine
-    };
-    let substract_one_if_not_0 = |x: usize| if x > 0 { x - 1 } else { 0 };
-    if matches_a.first().unwrap().start_a > 1 && matches_b.first().unwrap().start_b > 1 {
-        result.push(add_cosine_similarity_to_result(&mut utils::Result {
-            a: utils::Substring {
-                start: 0,
-                end: token_a[matches_a.first().unwrap().start_a-1].end,
-            },
-            b: utils::Substring {
-                start: 0,
-                end: token_b[matches_b.first().unwrap().start_b-1].end,
-            },
-            similarity: 0.0,
-            levenshteinMatch: false,
-        }));
+    pub fn set_synonyms(&mut self, synonyms_a: JsValue, synonyms_b: JsValue) -> std::result::Result<(), Thrown> {
+        self.inner.set_synonyms(read_synonyms(synonyms_a)?, read_synonyms(synonyms_b)?);
+        Ok(())
+    }
+    pub fn run(&mut self, on_progress: Option<ProgressCallback>) -> std::result::Result<JsValue, Thrown> {
+        let response = self.inner.run(&mut monitor(on_progress))?;
+        to_js(&response)
     }
-    for (tokens_a, tokens_b) in matches_a.windows(2).zip(matches_b.windows(2)) {
-        // Get the area between two matches in from tokens_a and b
-        let mut cosine = utils::Result {
-            a: utils::Substring {
-                start: token_a[min(tokens_a[0].end_a, token_a.len() - 1)].start,
-                end: token_a[substract_one_if_not_0(tokens_a[1].start_a)].end,
-            },
-            b: utils::Substring {
-                start: token_b[min(tokens_b[0].end_b, token_b.len() - 1)].start,
-                end: token_b[substract_one_if_not_0(tokens_b[1].start_b)].end,
-            },
-            similarity: 0.0,
-            levenshteinMatch: false,
-        };
-        result.push(add_cosine_similarity_to_result(&mut cosine));
+}
+
+fn monitor(on_progress: Option<ProgressCallback>) -> Monitor<'static> {
+    match on_progress {
+        Some(callback) => progress_monitor(callback.unchecked_into()),
+        None => Monitor::default(),
     }
-    if matches_a.last().unwrap().end_a < token_a.len() - 1
-        && matches_b.last().unwrap().end_b < token_b.len() - 1
-    {
-        result.push(add_cosine_similarity_to_result(&mut utils::Result {
-            a: utils::Substring {
-                start: token_a[matches_a.last().unwrap().end_a].start,
-                end: file_a.len(),
-            },
-            b: utils::Substring {
-                start: token_b[matches_b.last().unwrap().end_b].start,
-                end: file_b.len(),
-            },
-            similarity: 0.0,
-            levenshteinMatch: false,
-        }));
+}
+
+// A callback that throws also cancels, otherwise the exception would be lost
+fn progress_monitor(callback: js_sys::Function) -> Monitor<'static> {
+    let cancellation = CancellationToken::new();
+    let token = cancellation.clone();
+    Monitor::new(
+        move |progress| {
+            let progress = JsValue::from_serde(&progress).unwrap_or(JsValue::UNDEFINED);
+            let cancel = match callback.call1(&JsValue::NULL, &progress) {
+                Ok(result) => result.as_bool() == Some(false),
+                Err(_) => true,
+            };
+            if cancel {
+                token.cancel();
+            }
+        },
+        cancellation,
+    )
+}
+
+// Missing fields, or a missing config altogether, fall back to the defaults
+fn read_config(config: JsValue) -> std::result::Result<AnalysisConfig, Error> {
+    if config.is_undefined() || config.is_null() {
+        return Ok(AnalysisConfig::default());
     }
-    return JsValue::from_serde(&ResponseAndOverall {
-        overall_levenstein_similarity: utils::recompute_ratio(&token_a, &token_b, 0, token_a.len(), 0, token_b.len(), utils::find_length_from_tokens(&token_a, &token_b)),
-        overall_cosine_similarity: utils::cosine_similarity(&file_a.as_slice(), &file_b.as_slice()),
-        result,
-    }).unwrap();
+    config.into_serde::<AnalysisConfig>().map_err(|e| Error::InvalidParameter {
+        name: "config",
+        reason: e.to_string(),
+    })
 }
 
-const PUNCTUATION: [char; 44] = [
-    '.', ',', '，', '。', '：', '；', '「', '」', '？', '\n', '、', '·', '》', '《', '“', '”', '‘',
-    '’', '！', '（', '）', '【', '】', '『', '』', '—', '～', '\n', '\r', '\t', ' ', '*', '!', '?',
-    ':', ';', '(', ')', '[', ']', '{', '}', '<', '>',
-];
+fn read_synonyms(synonyms: JsValue) -> std::result::Result<Vec<synonyms::Synonym>, Error> {
+    synonyms
+        .into_serde::<Vec<synonyms::Synonym>>()
+        .map_err(|e| Error::MalformedSynonym { reason: e.to_string() })
+}
+
+// Splits a table of variant characters into the groups expected by `AnalysisConfig.variants`
+#[wasm_bindgen]
+pub fn parse_variant_groups(table: String) -> Vec<String> {
+    substr_core::variants::parse_variant_groups(&table)
+}
+
+// Splits a glossary into the groups of word forms expected by `AnalysisConfig.dictionary`
+#[wasm_bindgen(unchecked_return_type = "{ forms: string[]; cost: number }[]")]
+pub fn parse_dictionary(table: String) -> std::result::Result<JsValue, JsError> {
+    Ok(JsValue::from_serde(&substr_core::dictionary::parse_dictionary(&table))?)
+}
+
+// Groups the characters of a reading table into the homophones expected by `AnalysisConfig.phonetic.readings`
+#[wasm_bindgen]
+pub fn parse_readings(table: String) -> Vec<String> {
+    substr_core::phonetic::parse_readings(&table)
+}
 
 #[wasm_bindgen]
 pub fn clean_text(text: String) -> String {
-    let mut text = text;
-    text.retain(|c| !PUNCTUATION.contains(&c));
-    text
+    substr_core::clean_text(&text)
 }
 
 #[wasm_bindgen(start)]